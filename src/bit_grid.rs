@@ -0,0 +1,249 @@
+use crate::fill::{ReadTiles, Tiles};
+use crate::grid::{Bounded, Grid, TileAddress, TileRect};
+
+const WORD_BITS: u32 = u64::BITS;
+
+/// A grid of booleans packed one bit per tile, for masks like "is land" or "is reachable".
+/// Set operations work a whole word (64 tiles) at a time.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BitGrid {
+    width: u32,
+    height: u32,
+    // row by row from the bottom, like `Grid`; bits past the last tile are always clear
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    /// A grid with every bit clear
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width as usize * height as usize).div_ceil(WORD_BITS as usize);
+        BitGrid {
+            width,
+            height,
+            words: vec![0; len],
+        }
+    }
+
+    /// A grid with every bit set to `value`
+    pub fn filled(width: u32, height: u32, value: bool) -> Self {
+        let mut bits = BitGrid::new(width, height);
+        if value {
+            bits.invert();
+        }
+        bits
+    }
+
+    /// Sets the bits for the tiles of `grid` that satisfy the `predicate`
+    pub fn from_grid<T, P: Fn(&T) -> bool>(grid: &Grid<T>, predicate: P) -> Self {
+        let mut bits = BitGrid::new(grid.width(), grid.height());
+        for (addr, tile) in grid.entries() {
+            if predicate(tile) {
+                bits.set(&addr, true);
+            }
+        }
+        bits
+    }
+
+    /// A grid where each tile is `f` of its bit
+    pub fn to_grid<T, F: Fn(bool) -> T>(&self, f: F) -> Grid<T> {
+        Grid::from_fn(self.width, self.height, |addr| f(self.get(&addr)))
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+
+    pub fn height(&self) -> u32 { self.height }
+
+    pub fn contains(&self, pos: &TileAddress) -> bool {
+        pos.0 < self.width && pos.1 < self.height
+    }
+
+    /// Whether the bit at `pos` is set. Beyond the edges of the grid counts as clear.
+    pub fn get(&self, pos: &TileAddress) -> bool {
+        match self.locate(pos) {
+            Some((word, mask)) => self.words[word] & mask != 0,
+            None => false,
+        }
+    }
+
+    /// Sets or clears the bit at `pos`, if it's on the grid
+    pub fn set(&mut self, pos: &TileAddress, value: bool) {
+        if let Some((word, mask)) = self.locate(pos) {
+            if value {
+                self.words[word] |= mask;
+            } else {
+                self.words[word] &= !mask;
+            }
+        }
+    }
+
+    /// Sets the bits that are set in either grid
+    pub fn union_with(&mut self, other: &BitGrid) {
+        self.combine(other, |a, b| a | b);
+    }
+
+    /// Clears the bits that aren't set in both grids
+    pub fn intersect_with(&mut self, other: &BitGrid) {
+        self.combine(other, |a, b| a & b);
+    }
+
+    /// Clears the bits that are set in `other`
+    pub fn difference_with(&mut self, other: &BitGrid) {
+        self.combine(other, |a, b| a & !b);
+    }
+
+    pub fn invert(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = !*word;
+        }
+        self.clear_padding();
+    }
+
+    /// How many bits are set
+    pub fn count_ones(&self) -> u32 {
+        self.words.iter().map(|word| word.count_ones()).sum()
+    }
+
+    /// Whether no bits are set
+    pub fn is_clear(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// The addresses of the set bits, row by row from the bottom
+    pub fn ones(&self) -> impl Iterator<Item=TileAddress> + '_ {
+        let width = self.width as usize;
+        self.words.iter().enumerate().flat_map(move |(i, &word)| {
+            let mut remaining = word;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+                let bit = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                let index = i * WORD_BITS as usize + bit;
+                Some(TileAddress((index % width) as u32, (index / width) as u32))
+            })
+        })
+    }
+
+    fn locate(&self, pos: &TileAddress) -> Option<(usize, u64)> {
+        if self.contains(pos) {
+            let index = pos.1 as usize * self.width as usize + pos.0 as usize;
+            Some((index / WORD_BITS as usize, 1 << (index % WORD_BITS as usize)))
+        } else {
+            None
+        }
+    }
+
+    fn combine<F: Fn(u64, u64) -> u64>(&mut self, other: &BitGrid, op: F) {
+        assert!(self.width == other.width && self.height == other.height, "combined bit grids must be the same size");
+        for (word, &other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word = op(*word, other);
+        }
+        self.clear_padding();
+    }
+
+    fn clear_padding(&mut self) {
+        let used = (self.width as usize * self.height as usize) % WORD_BITS as usize;
+        if let (Some(last), true) = (self.words.last_mut(), used > 0) {
+            *last &= (1 << used) - 1;
+        }
+    }
+}
+
+impl Bounded for BitGrid {
+    fn bounds(&self) -> TileRect {
+        TileRect::new(TileAddress(0, 0), self.width, self.height)
+    }
+}
+
+impl ReadTiles<u32> for BitGrid {
+    type Tile = bool;
+    fn get_tile(&self, x: u32, y: u32) -> Option<&bool> {
+        let pos = TileAddress(x, y);
+        self.contains(&pos).then(|| if self.get(&pos) { &true } else { &false })
+    }
+}
+
+impl Tiles<u32> for BitGrid {
+    fn set_tile(&mut self, x: u32, y: u32, tile: bool) {
+        self.set(&TileAddress(x, y), tile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 13x7 = 91 tiles, so the second word only has 27 of its bits in use
+    const WIDTH: u32 = 13;
+    const HEIGHT: u32 = 7;
+
+    fn pattern() -> Grid<bool> {
+        Grid::from_fn(WIDTH, HEIGHT, |TileAddress(x, y)| (x * 7 + y * 3) % 5 < 2)
+    }
+
+    #[test]
+    fn bits_read_back_the_way_they_were_set() {
+        let expected = pattern();
+        let mut bits = BitGrid::new(WIDTH, HEIGHT);
+        for (addr, &tile) in expected.entries() {
+            bits.set(&addr, tile);
+        }
+        assert_eq!(bits.to_grid(|bit| bit), expected);
+        assert_eq!(bits, BitGrid::from_grid(&expected, |tile| *tile));
+        assert_eq!(bits.count_ones() as usize, expected.entries().filter(|(_, tile)| **tile).count());
+        assert_eq!(bits.ones().collect::<Vec<_>>(), expected.addresses().filter(|addr| expected[*addr]).collect::<Vec<_>>());
+
+        // including the very last tile, and clearing it again
+        let last = TileAddress(WIDTH - 1, HEIGHT - 1);
+        bits.set(&last, true);
+        assert!(bits.get(&last));
+        bits.set(&last, false);
+        assert!(!bits.get(&last));
+    }
+
+    #[test]
+    fn tiles_beyond_the_grid_are_clear_and_stay_that_way() {
+        let mut bits = BitGrid::new(WIDTH, HEIGHT);
+        for pos in [TileAddress(WIDTH, 0), TileAddress(0, HEIGHT), TileAddress(WIDTH, HEIGHT - 1)] {
+            bits.set(&pos, true);
+            assert!(!bits.get(&pos));
+            assert_eq!(bits.get_tile(pos.0, pos.1), None);
+        }
+        assert!(bits.is_clear());
+    }
+
+    #[test]
+    fn padding_bits_are_never_counted() {
+        assert_eq!(BitGrid::filled(WIDTH, HEIGHT, true).count_ones(), WIDTH * HEIGHT);
+        assert_eq!(BitGrid::filled(8, 8, true).count_ones(), 64);
+        assert_eq!(BitGrid::filled(0, 5, true).count_ones(), 0);
+
+        let mut inverted = BitGrid::from_grid(&pattern(), |tile| *tile);
+        let set = inverted.count_ones();
+        inverted.invert();
+        assert_eq!(inverted.count_ones(), WIDTH * HEIGHT - set);
+        assert_eq!(inverted.ones().count() as u32, WIDTH * HEIGHT - set);
+        assert!(inverted.ones().all(|addr| inverted.contains(&addr)));
+    }
+
+    #[test]
+    fn set_operations_match_tile_by_tile() {
+        let a = BitGrid::from_grid(&pattern(), |tile| *tile);
+        let b = BitGrid::from_grid(&Grid::from_fn(WIDTH, HEIGHT, |TileAddress(x, y)| x > y), |tile| *tile);
+        // each operation, and what it does to a single pair of bits
+        type Op = (fn(&mut BitGrid, &BitGrid), fn(bool, bool) -> bool);
+        let ops: [Op; 3] = [
+            (BitGrid::union_with, |a, b| a | b),
+            (BitGrid::intersect_with, |a, b| a & b),
+            (BitGrid::difference_with, |a, b| a & !b),
+        ];
+        for (op, expected) in ops {
+            let mut combined = a.clone();
+            op(&mut combined, &b);
+            let expected = Grid::from_fn(WIDTH, HEIGHT, |addr| expected(a.get(&addr), b.get(&addr)));
+            assert_eq!(combined.to_grid(|bit| bit), expected);
+            assert_eq!(combined.count_ones() as usize, expected.entries().filter(|(_, tile)| **tile).count());
+        }
+    }
+}
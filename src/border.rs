@@ -1,39 +1,41 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
 use bevy::prelude::{Component, Vec2};
 use bevy_rapier2d::rapier::prelude::Aabb;
 use parry2d::math::{Point, Vector};
 
 use crate::cardinal::Cardinal;
-use crate::grid::{Grid, TileAddress};
+use crate::fill::ReadTiles;
+use crate::grid::{Bounded, Grid, TileAddress};
 use crate::zone::GridDimensions;
 
-#[derive(Component, Debug, Copy, Clone)]
-pub struct Border {
+/// One side of a tile, whether or not it's actually a border.
+/// Named by the tile it's the south or west side of, so neighboring tiles agree on it.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Edge {
     pos: TileAddress,
     is_vertical: bool,
 }
 
-impl Border {
-    fn at(pos: TileAddress, cardinal: Cardinal) -> Self {
-        match cardinal {
-            Cardinal::North => Border {
-                pos: TileAddress(pos.0, pos.1 + 1),
-                is_vertical: false,
-            },
-            Cardinal::East => Border {
-                pos: TileAddress(pos.0 + 1, pos.1),
-                is_vertical: true,
-            },
-            Cardinal::South => Border {
-                pos,
-                is_vertical: false,
-            },
-            Cardinal::West => Border {
-                pos,
-                is_vertical: true,
-            }
+impl Edge {
+    /// The edge on the `cardinal` side of the tile at `pos`
+    pub fn of(pos: TileAddress, cardinal: Cardinal) -> Self {
+        // north and east edges are named by the neighbor they're the south or west edge of
+        let pos = match cardinal {
+            Cardinal::North | Cardinal::East => pos.step(cardinal).expect("tile address overflowed"),
+            Cardinal::South | Cardinal::West => pos,
+        };
+        Edge {
+            pos,
+            is_vertical: cardinal.is_east_west(),
         }
     }
 
+    /// The four edges of a tile
+    pub fn around(pos: TileAddress) -> [Edge; 4] {
+        Cardinal::ALL.map(|cardinal| Edge::of(pos, cardinal))
+    }
+
     pub fn pos(&self) -> &TileAddress {
         &self.pos
     }
@@ -42,9 +44,134 @@ impl Border {
         self.is_vertical
     }
 
+    /// The tiles on either side of this edge: west then east for a vertical edge,
+    /// south then north for a horizontal one. Either may be outside of the grid.
+    pub fn sides(&self) -> (Option<TileAddress>, TileAddress) {
+        let TileAddress(x, y) = self.pos;
+        if self.is_vertical {
+            (x.checked_sub(1).map(|x| TileAddress(x, y)), self.pos)
+        } else {
+            (y.checked_sub(1).map(|y| TileAddress(x, y)), self.pos)
+        }
+    }
+
+    /// Whether this edge separates an inside tile from an outside one.
+    /// Tiles beyond the edges of the grid count as outside.
+    pub fn is_border_in<G, F>(&self, grid: &G, test_inside: &F) -> bool
+        where G: ReadTiles<u32>,
+              F: Fn(&G::Tile) -> bool
+    {
+        let (a, b) = self.sides();
+        let a_inside = a.and_then(|a| grid.get_tile(a.0, a.1)).is_some_and(test_inside);
+        let b_inside = grid.get_tile(b.0, b.1).is_some_and(test_inside);
+        a_inside != b_inside
+    }
+
+    /// The border along this edge, if `classify` says there is one.
+    ///
+    /// `classify` is given an inside candidate and the tile across the edge from it (`None` beyond
+    /// the edge of the grid), and returns the kind of border between them, if any. Each tile
+    /// gets a turn as the inside candidate, the north or east one first.
+    pub fn border_by<G, F>(&self, grid: &G, classify: &F) -> Option<Border>
+        where G: ReadTiles<u32>,
+              F: Fn(&G::Tile, Option<&G::Tile>) -> Option<BorderKind>
+    {
+        let (low, high) = self.sides();
+        let low_tile = low.and_then(|low| grid.get_tile(low.0, low.1));
+        let high_tile = grid.get_tile(high.0, high.1);
+        let towards_low = if self.is_vertical { Cardinal::West } else { Cardinal::South };
+        let towards_high = towards_low.opposite();
+
+        if let Some(kind) = high_tile.and_then(|high| classify(high, low_tile)) {
+            return Some(Border { edge: *self, facing: towards_low, kind });
+        }
+        if let Some(kind) = low_tile.and_then(|low| classify(low, high_tile)) {
+            return Some(Border { edge: *self, facing: towards_high, kind });
+        }
+        None
+    }
+}
+
+/// What a border separates
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum BorderKind {
+    /// Between an inside tile and the edge of the grid
+    MapEdge,
+    /// Between an inside tile and an outside region that reaches the edge of the grid
+    Coast,
+    /// Between an inside tile and an outside region that's completely enclosed
+    Lake,
+    /// Anything else, as decided by a classifier passed to `collect_borders_by`
+    Custom(u32),
+}
+
+/// A tile edge with an inside tile on one side and an outside tile (or the edge of the grid) on the other.
+#[derive(Component, Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct Border {
+    edge: Edge,
+    facing: Cardinal,
+    kind: BorderKind,
+}
+
+impl Border {
+    /// The border on the `facing` side of the inside tile at `pos`
+    pub fn at(pos: TileAddress, facing: Cardinal, kind: BorderKind) -> Self {
+        Border {
+            edge: Edge::of(pos, facing),
+            facing,
+            kind,
+        }
+    }
+
+    pub fn edge(&self) -> &Edge {
+        &self.edge
+    }
+
+    pub fn pos(&self) -> &TileAddress {
+        &self.edge.pos
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        self.edge.is_vertical
+    }
+
+    /// The outward normal: the direction from the inside tile to the outside one
+    pub fn facing(&self) -> Cardinal {
+        self.facing
+    }
+
+    pub fn kind(&self) -> BorderKind {
+        self.kind
+    }
+
+    pub fn inside_tile(&self) -> TileAddress {
+        let (low, high) = self.edge.sides();
+        match self.facing {
+            Cardinal::West | Cardinal::South => high,
+            // a border facing east or north always has its inside tile on the grid
+            Cardinal::East | Cardinal::North => low.unwrap(),
+        }
+    }
+
+    /// The tile across the border from the inside one. May be beyond the edge of the grid,
+    /// or `None` for the west and south edges of the grid.
+    pub fn outside_tile(&self) -> Option<TileAddress> {
+        let (low, high) = self.edge.sides();
+        match self.facing {
+            Cardinal::West | Cardinal::South => low,
+            Cardinal::East | Cardinal::North => Some(high),
+        }
+    }
+
+    /// The inside and outside tiles' values in `grid`
+    pub fn tiles_in<'g, T>(&self, grid: &'g Grid<T>) -> (Option<&'g T>, Option<&'g T>) {
+        let outside = self.outside_tile().and_then(|outside| grid.tile_at(&outside));
+        (grid.tile_at(&self.inside_tile()), outside)
+    }
+
     pub fn get_aabb(&self, dims: &GridDimensions, radius: f32) -> Aabb {
         let tile_size = dims.tile_size;
-        let Vec2 { x, y } = dims.world_pos_of(&self.pos);
+        let Vec2 { x, y } = dims.world_pos_of(self.pos());
         let small_dim = tile_size * radius;
         let large_dim = tile_size * (0.5 + radius);
         let half_tile = tile_size * 0.5;
@@ -62,46 +189,370 @@ impl Border {
     }
 }
 
-pub fn collect_borders<T, F, FB>(grid: &Grid<T>, test_inside: &F, receiver: &mut FB)
-    where F: Fn(&T) -> bool,
+/// Collects every border between inside and outside tiles. Borders with the edge of the grid are
+/// `MapEdge`s, and the rest are `Coast`s or `Lake`s depending on whether the outside region
+/// they face reaches the edge of the grid.
+pub fn collect_borders<G, F, FB>(grid: &G, test_inside: &F, receiver: &mut FB)
+    where G: ReadTiles<u32> + Bounded,
+          F: Fn(&G::Tile) -> bool,
           FB: FnMut(Border) -> ()
 {
-    for addr in grid.addresses() {
-        if let Some(tile) = grid.tile_at(&addr) {
-            if test_inside(tile) {
-                if addr.0 == 0 {
-                    // west wall of X=0 is always a border
-                    receiver(Border::at(addr, Cardinal::West));
-                }
-                if addr.1 == 0 {
-                    // south wall of Y=0 is always a border
-                    receiver(Border::at(addr, Cardinal::South));
-                }
+    let open = open_outside(grid, test_inside);
+    collect_unclassified_borders(grid, test_inside, &mut |border| {
+        receiver(mark_lake(border, &open))
+    });
+}
 
-                // if tile to the North is not inside, that's a border
-                let to_north = TileAddress(addr.0, addr.1 + 1);
-                if !grid.tile_at(&to_north).is_some_and(test_inside) {
-                    receiver(Border::at(addr, Cardinal::North));
-                }
+/// Like `collect_borders`, but without telling lakes from coasts: every border that isn't a
+/// `MapEdge` is a `Coast`. That saves flooding the outside of the whole grid.
+pub fn collect_unclassified_borders<G, F, FB>(grid: &G, test_inside: &F, receiver: &mut FB)
+    where G: ReadTiles<u32> + Bounded,
+          F: Fn(&G::Tile) -> bool,
+          FB: FnMut(Border) -> ()
+{
+    collect_borders_by(grid, &inside_classifier(test_inside), receiver);
+}
 
-                // if tile to the East is not inside, that's a border
-                let to_east = TileAddress(addr.0 + 1, addr.1);
-                if !grid.tile_at(&to_east).is_some_and(test_inside) {
-                    receiver(Border::at(addr, Cardinal::East));
+/// Sets each `Coast` or `Lake` border's kind by whether the outside region it faces reaches the
+/// edge of the grid, as `collect_borders` would. Flooding the outside takes a pass over the whole
+/// grid, so this is for borders that were collected without it, and only where the kind matters.
+pub fn classify_lakes<G, F>(grid: &G, test_inside: &F, borders: &mut [Border])
+    where G: ReadTiles<u32> + Bounded,
+          F: Fn(&G::Tile) -> bool
+{
+    let open = open_outside(grid, test_inside);
+    for border in borders.iter_mut() {
+        *border = mark_lake(*border, &open);
+    }
+}
+
+/// Collects every border that `classify` finds; see `Edge::border_by`.
+/// This is how grids with more than two kinds of tile decide which pairs of tiles get a border.
+pub fn collect_borders_by<G, F, FB>(grid: &G, classify: &F, receiver: &mut FB)
+    where G: ReadTiles<u32> + Bounded,
+          F: Fn(&G::Tile, Option<&G::Tile>) -> Option<BorderKind>,
+          FB: FnMut(Border) -> ()
+{
+    let bounds = grid.bounds();
+    for addr in bounds.addresses() {
+        let west_and_south = [Edge::of(addr, Cardinal::West), Edge::of(addr, Cardinal::South)];
+        // the east and north edges of the grid don't belong to any tile's west or south
+        let east = (addr.0 + 1 == bounds.width).then(|| Edge::of(addr, Cardinal::East));
+        let north = (addr.1 + 1 == bounds.height).then(|| Edge::of(addr, Cardinal::North));
+
+        for edge in west_and_south.into_iter().chain(east).chain(north) {
+            if let Some(border) = edge.border_by(grid, classify) {
+                receiver(border);
+            }
+        }
+    }
+}
+
+/// Like `collect_unclassified_borders`, but only looks at the edges of the `dirty` tiles, e.g. the
+/// tiles that changed this frame. Each border is received once, even if both of its tiles are dirty.
+///
+/// Lakes aren't told from coasts here: an edit can open up or close off a lake anywhere on the grid,
+/// which would change borders far from the dirty tiles. Use `classify_lakes` where that matters.
+pub fn collect_borders_around<G, F, FB, I>(grid: &G, dirty: I, test_inside: &F, receiver: &mut FB)
+    where G: ReadTiles<u32> + Bounded,
+          F: Fn(&G::Tile) -> bool,
+          FB: FnMut(Border) -> (),
+          I: IntoIterator<Item=TileAddress>
+{
+    let classify = inside_classifier(test_inside);
+    let mut seen = HashSet::new();
+    for addr in dirty {
+        for edge in Edge::around(addr) {
+            if seen.insert(edge) {
+                if let Some(border) = edge.border_by(grid, &classify) {
+                    receiver(border);
                 }
+            }
+        }
+    }
+}
+
+// borders between inside tiles and anything else, not yet telling coasts from lakes
+fn inside_classifier<T, F>(test_inside: &F) -> impl Fn(&T, Option<&T>) -> Option<BorderKind> + '_
+    where F: Fn(&T) -> bool
+{
+    move |tile, across| {
+        if !test_inside(tile) {
+            return None;
+        }
+        match across {
+            None => Some(BorderKind::MapEdge),
+            Some(across) if test_inside(across) => None,
+            Some(_) => Some(BorderKind::Coast),
+        }
+    }
+}
+
+// a coast or lake border that faces an enclosed outside tile is a lake; other kinds are left alone
+fn mark_lake(border: Border, open: &Grid<bool>) -> Border {
+    if !matches!(border.kind, BorderKind::Coast | BorderKind::Lake) {
+        return border;
+    }
+    let enclosed = border.outside_tile()
+        .and_then(|outside| open.tile_at(&outside))
+        .is_some_and(|open| !open);
+    let kind = if enclosed { BorderKind::Lake } else { BorderKind::Coast };
+    Border { kind, ..border }
+}
+
+// which outside tiles are connected (orthogonally) to the edge of the grid
+fn open_outside<G, F>(grid: &G, test_inside: &F) -> Grid<bool>
+    where G: ReadTiles<u32> + Bounded,
+          F: Fn(&G::Tile) -> bool
+{
+    let bounds = grid.bounds();
+    let (width, height) = (bounds.width, bounds.height);
+    let mut open = Grid::new(width, height);
+    let mut queue: VecDeque<TileAddress> = bounds.addresses()
+        .filter(|addr| addr.0 == 0 || addr.1 == 0 || addr.0 + 1 == width || addr.1 + 1 == height)
+        .collect();
+    while let Some(addr) = queue.pop_front() {
+        if open[addr] || grid.get_tile(addr.0, addr.1).is_some_and(test_inside) {
+            continue;
+        }
+        open[addr] = true;
+        queue.extend(open.neighbors4(addr));
+    }
+    open
+}
+
+/// A straight run of adjacent `Border`s that all face the same way and are of the same kind,
+/// so that a long wall can be one collider instead of one per tile edge.
+#[derive(Component, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BorderSegment {
+    start: TileAddress,
+    length: u32,
+    facing: Cardinal,
+    kind: BorderKind,
+}
+
+impl BorderSegment {
+    /// Position of the segment's first edge, i.e. its bottom or left end
+    pub fn start(&self) -> &TileAddress {
+        &self.start
+    }
+
+    /// Number of tile edges covered by the segment
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Direction from the inside of the segment to the outside
+    pub fn facing(&self) -> Cardinal {
+        self.facing
+    }
+
+    pub fn kind(&self) -> BorderKind {
+        self.kind
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        self.facing.is_east_west()
+    }
+
+    /// The individual borders that make up this segment
+    pub fn borders(&self) -> impl Iterator<Item=Border> {
+        let TileAddress(x, y) = self.start;
+        let (facing, kind) = (self.facing, self.kind);
+        let is_vertical = self.is_vertical();
+        (0..self.length).map(move |i| Border {
+            edge: Edge {
+                pos: if is_vertical { TileAddress(x, y + i) } else { TileAddress(x + i, y) },
+                is_vertical,
+            },
+            facing,
+            kind,
+        })
+    }
+
+    pub fn get_aabb(&self, dims: &GridDimensions, radius: f32) -> Aabb {
+        let tile_size = dims.tile_size;
+        let Vec2 { x, y } = dims.world_pos_of(&self.start);
+        let small_dim = tile_size * radius;
+        let half_length = tile_size * self.length as f32 * 0.5;
+        let large_dim = half_length + small_dim;
+        if self.is_vertical() {
+            Aabb::from_half_extents(
+                Point::new(x, y + half_length),
+                Vector::new(small_dim, large_dim),
+            )
+        } else {
+            Aabb::from_half_extents(
+                Point::new(x + half_length, y),
+                Vector::new(large_dim, small_dim),
+            )
+        }
+    }
+}
+
+/// Merges runs of adjacent, collinear `borders` that face the same way and are of the same kind
+/// into `BorderSegment`s.
+pub fn merge_borders<I>(borders: I) -> Vec<BorderSegment>
+    where I: IntoIterator<Item=Border>
+{
+    // group the borders by which line they're on, which way they face and what kind they are,
+    // keeping only their position along that line
+    let mut lines: HashMap<(Cardinal, BorderKind, u32), Vec<u32>> = HashMap::new();
+    for border in borders {
+        let TileAddress(x, y) = *border.pos();
+        let (line, along) = if border.is_vertical() { (x, y) } else { (y, x) };
+        lines.entry((border.facing, border.kind, line)).or_default().push(along);
+    }
+
+    let mut segments = Vec::new();
+    for ((facing, kind, line), mut positions) in lines {
+        positions.sort_unstable();
+        positions.dedup();
+        let is_vertical = facing.is_east_west();
+        let mut push_run = |first: u32, length: u32| {
+            let start = if is_vertical { TileAddress(line, first) } else { TileAddress(first, line) };
+            segments.push(BorderSegment { start, length, facing, kind });
+        };
+
+        let mut run_start = positions[0];
+        let mut run_length = 1;
+        for &pos in &positions[1..] {
+            if pos == run_start + run_length {
+                run_length += 1;
             } else {
-                // if the tile to the North is inside, that's a border
-                let to_north = TileAddress(addr.0, addr.1 + 1);
-                if grid.tile_at(&to_north).is_some_and(test_inside) {
-                    receiver(Border::at(to_north, Cardinal::South));
-                }
+                push_run(run_start, run_length);
+                run_start = pos;
+                run_length = 1;
+            }
+        }
+        push_run(run_start, run_length);
+    }
+    segments
+}
 
-                // if the tile to the East is inside, that's a border
-                let to_east = TileAddress(addr.0 + 1, addr.1);
-                if grid.tile_at(&to_east).is_some_and(test_inside) {
-                    receiver(Border::at(to_east, Cardinal::West));
-                }
+/// `collect_unclassified_borders`, merged into as few `BorderSegment`s as possible. Walls don't
+/// care what they face, and this way they match the borders that `collect_borders_around` finds.
+pub fn collect_border_segments<G, F>(grid: &G, test_inside: &F) -> Vec<BorderSegment>
+    where G: ReadTiles<u32> + Bounded,
+          F: Fn(&G::Tile) -> bool
+{
+    let mut borders = Vec::new();
+    collect_unclassified_borders(grid, test_inside, &mut |border| borders.push(border));
+    merge_borders(borders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge_set(grid: &Grid<bool>) -> HashMap<Edge, Border> {
+        let mut borders = HashMap::new();
+        collect_borders(grid, &|tile: &bool| *tile, &mut |border: Border| {
+            borders.insert(border.edge, border);
+        });
+        borders
+    }
+
+    #[test]
+    fn incremental_updates_match_a_fresh_collection() {
+        // an island with a one-tile lake in the middle of it
+        let mut grid = Grid::<bool>::new(7, 7);
+        for addr in grid.addresses().collect::<Vec<_>>() {
+            grid[addr] = (1..=5).contains(&addr.0) && (1..=5).contains(&addr.1) && addr != TileAddress(3, 3);
+        }
+        let mut borders = edge_set(&grid);
+        assert!(borders.values().any(|border| border.kind == BorderKind::Lake));
+
+        // cutting a channel from the lake to the sea, one tile at a time
+        for x in [4, 5] {
+            let dirty = TileAddress(x, 3);
+            grid[dirty] = false;
+            for edge in Edge::around(dirty) {
+                borders.remove(&edge);
             }
+            collect_borders_around(&grid, [dirty], &|tile: &bool| *tile, &mut |border: Border| {
+                borders.insert(border.edge, border);
+            });
+
+            let fresh = edge_set(&grid);
+            assert_eq!(borders.keys().collect::<HashSet<_>>(), fresh.keys().collect::<HashSet<_>>());
+
+            // borders away from the channel keep their old kind until they're classified again
+            let mut classified: Vec<Border> = borders.values().copied().collect();
+            classify_lakes(&grid, &|tile: &bool| *tile, &mut classified);
+            assert_eq!(classified.into_iter().collect::<HashSet<_>>(), fresh.into_values().collect::<HashSet<_>>());
         }
+        // by now the lake has reached the sea
+        assert!(edge_set(&grid).values().all(|border| border.kind != BorderKind::Lake));
+    }
+
+    fn has_segment(segments: &[BorderSegment], start: TileAddress, length: u32, facing: Cardinal, kind: BorderKind) -> bool {
+        segments.contains(&BorderSegment { start, length, facing, kind })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn merges_adjacent_collinear_borders() {
+        let coast = BorderKind::Coast;
+        // the north sides of a row of tiles with a gap in it, given out of order and with a repeat
+        let mut borders: Vec<Border> = [3, 1, 2, 5, 2].map(|x| Border::at(TileAddress(x, 1), Cardinal::North, coast)).into();
+        // on the same line, but facing the other way or of another kind
+        borders.push(Border::at(TileAddress(2, 2), Cardinal::South, coast));
+        borders.push(Border::at(TileAddress(4, 1), Cardinal::North, BorderKind::Lake));
+        // and a vertical run, on the east side of a column
+        borders.extend((0..4).map(|y| Border::at(TileAddress(3, y), Cardinal::East, coast)));
+
+        let segments = merge_borders(borders.iter().copied());
+        assert_eq!(segments.len(), 5);
+        assert!(has_segment(&segments, TileAddress(1, 2), 3, Cardinal::North, coast));
+        assert!(has_segment(&segments, TileAddress(5, 2), 1, Cardinal::North, coast));
+        assert!(has_segment(&segments, TileAddress(2, 2), 1, Cardinal::South, coast));
+        assert!(has_segment(&segments, TileAddress(4, 2), 1, Cardinal::North, BorderKind::Lake));
+        assert!(has_segment(&segments, TileAddress(4, 0), 4, Cardinal::East, coast));
+
+        // and splitting them back up gives every border once
+        let split: HashSet<Border> = segments.iter().flat_map(|segment| segment.borders()).collect();
+        assert_eq!(split, borders.into_iter().collect());
+        assert!(merge_borders([]).is_empty());
+    }
+
+    #[test]
+    fn custom_classifiers_pick_the_border_kinds() {
+        const WATER: u8 = 0;
+        const SAND: u8 = 1;
+        const ROCK: u8 = 2;
+        let classify = |tile: &u8, across: Option<&u8>| match (*tile, across.copied()) {
+            (WATER, _) => None,
+            (_, None) => Some(BorderKind::MapEdge),
+            (SAND, Some(WATER)) => Some(BorderKind::Coast),
+            (ROCK, Some(SAND)) => Some(BorderKind::Custom(1)),
+            (ROCK, Some(WATER)) => Some(BorderKind::Custom(2)),
+            _ => None,
+        };
+        let grid = Grid::from_fn(4, 1, |TileAddress(x, _)| [ROCK, SAND, WATER, ROCK][x as usize]);
+        let mut borders = Vec::new();
+        collect_borders_by(&grid, &classify, &mut |border: Border| borders.push(border));
+
+        let vertical: HashSet<(TileAddress, Cardinal, BorderKind)> = borders.iter()
+            .filter(|border| border.is_vertical())
+            .map(|border| (border.inside_tile(), border.facing(), border.kind()))
+            .collect();
+        assert_eq!(vertical, HashSet::from([
+            (TileAddress(0, 0), Cardinal::West, BorderKind::MapEdge),
+            (TileAddress(0, 0), Cardinal::East, BorderKind::Custom(1)),
+            (TileAddress(1, 0), Cardinal::East, BorderKind::Coast),
+            (TileAddress(3, 0), Cardinal::West, BorderKind::Custom(2)),
+            (TileAddress(3, 0), Cardinal::East, BorderKind::MapEdge),
+        ]));
+        // the tops and bottoms of every tile but the water are at the edge of the grid
+        let horizontal = borders.iter().filter(|border| !border.is_vertical());
+        assert!(horizontal.clone().all(|border| border.kind() == BorderKind::MapEdge));
+        assert_eq!(horizontal.count(), 6);
+
+        // where both tiles would have a border, the north or east one gets it
+        let either = |tile: &u8, across: Option<&u8>| across.is_some_and(|a| a != tile).then_some(BorderKind::Custom(*tile as u32));
+        let pair = Grid::from_fn(2, 1, |TileAddress(x, _)| x as u8);
+        let mut borders = Vec::new();
+        collect_borders_by(&pair, &either, &mut |border: Border| borders.push(border));
+        assert_eq!(borders, vec![Border::at(TileAddress(1, 0), Cardinal::West, BorderKind::Custom(1))]);
+    }
+}
@@ -1,3 +1,5 @@
+use bevy::prelude::Vec2;
+
 /// Error type meaning some direction could not be interpreted as a `Cardinal`
 #[derive(Debug)]
 pub struct NonCardinal;
@@ -5,10 +7,301 @@ pub struct NonCardinal;
 /// Enum for directions parallel to the X and Y axes.
 /// Represented as North, South, East, and West,
 /// but could be considered the same as Up, Down, Right, and Left.
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Cardinal {
     North,
     East,
     South,
     West,
-}
\ No newline at end of file
+}
+
+impl Cardinal {
+    /// All four directions, clockwise from North
+    pub const ALL: [Cardinal; 4] = [Cardinal::North, Cardinal::East, Cardinal::South, Cardinal::West];
+
+    pub fn iter() -> impl Iterator<Item=Cardinal> {
+        Self::ALL.into_iter()
+    }
+
+    /// The step in tiles (X, Y) that moves one tile in this direction. North is +Y.
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Cardinal::North => (0, 1),
+            Cardinal::East => (1, 0),
+            Cardinal::South => (0, -1),
+            Cardinal::West => (-1, 0),
+        }
+    }
+
+    pub fn as_vec2(&self) -> Vec2 {
+        let (x, y) = self.offset();
+        Vec2::new(x as f32, y as f32)
+    }
+
+    pub fn opposite(&self) -> Cardinal {
+        self.rotate_cw().rotate_cw()
+    }
+
+    pub fn rotate_cw(&self) -> Cardinal {
+        match self {
+            Cardinal::North => Cardinal::East,
+            Cardinal::East => Cardinal::South,
+            Cardinal::South => Cardinal::West,
+            Cardinal::West => Cardinal::North,
+        }
+    }
+
+    pub fn rotate_ccw(&self) -> Cardinal {
+        match self {
+            Cardinal::North => Cardinal::West,
+            Cardinal::East => Cardinal::North,
+            Cardinal::South => Cardinal::East,
+            Cardinal::West => Cardinal::South,
+        }
+    }
+
+    /// Whether this direction is along the X axis
+    pub fn is_east_west(&self) -> bool {
+        matches!(self, Cardinal::East | Cardinal::West)
+    }
+}
+
+/// Picks the axis the vector mostly points along. Vectors exactly between two
+/// directions (including the zero vector), or that aren't finite, are `NonCardinal`.
+impl TryFrom<Vec2> for Cardinal {
+    type Error = NonCardinal;
+
+    fn try_from(v: Vec2) -> Result<Self, Self::Error> {
+        if !v.is_finite() || v.x.abs() == v.y.abs() {
+            Err(NonCardinal)
+        } else if v.x.abs() > v.y.abs() {
+            Ok(if v.x > 0. { Cardinal::East } else { Cardinal::West })
+        } else {
+            Ok(if v.y > 0. { Cardinal::North } else { Cardinal::South })
+        }
+    }
+}
+
+impl TryFrom<Ordinal> for Cardinal {
+    type Error = NonCardinal;
+
+    fn try_from(ordinal: Ordinal) -> Result<Self, Self::Error> {
+        match ordinal {
+            Ordinal::North => Ok(Cardinal::North),
+            Ordinal::East => Ok(Cardinal::East),
+            Ordinal::South => Ok(Cardinal::South),
+            Ordinal::West => Ok(Cardinal::West),
+            _ => Err(NonCardinal),
+        }
+    }
+}
+
+/// tan(22.5°): a vector is closer to an axis than to a diagonal if its component across the axis
+/// is less than this much of its component along it
+const TAN_PI_8: f32 = 0.414_213_57;
+
+/// The eight directions to a tile's neighbors: the four `Cardinal`s and the diagonals between them.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Ordinal {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Ordinal {
+    /// All eight directions, clockwise from North
+    pub const ALL: [Ordinal; 8] = [
+        Ordinal::North,
+        Ordinal::NorthEast,
+        Ordinal::East,
+        Ordinal::SouthEast,
+        Ordinal::South,
+        Ordinal::SouthWest,
+        Ordinal::West,
+        Ordinal::NorthWest,
+    ];
+
+    pub fn iter() -> impl Iterator<Item=Ordinal> {
+        Self::ALL.into_iter()
+    }
+
+    /// Just the four diagonals, clockwise from NorthEast
+    pub fn diagonals() -> impl Iterator<Item=Ordinal> {
+        Self::iter().filter(Ordinal::is_diagonal)
+    }
+
+    /// The step in tiles (X, Y) that moves to the neighbor in this direction. North is +Y.
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Ordinal::North => (0, 1),
+            Ordinal::NorthEast => (1, 1),
+            Ordinal::East => (1, 0),
+            Ordinal::SouthEast => (1, -1),
+            Ordinal::South => (0, -1),
+            Ordinal::SouthWest => (-1, -1),
+            Ordinal::West => (-1, 0),
+            Ordinal::NorthWest => (-1, 1),
+        }
+    }
+
+    /// A unit vector in this direction
+    pub fn as_vec2(&self) -> Vec2 {
+        let (x, y) = self.offset();
+        Vec2::new(x as f32, y as f32).normalize()
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        self.cardinals().is_some()
+    }
+
+    /// The two `Cardinal`s a diagonal is made of, or `None` for the cardinal directions
+    pub fn cardinals(&self) -> Option<(Cardinal, Cardinal)> {
+        match self {
+            Ordinal::NorthEast => Some((Cardinal::North, Cardinal::East)),
+            Ordinal::SouthEast => Some((Cardinal::South, Cardinal::East)),
+            Ordinal::SouthWest => Some((Cardinal::South, Cardinal::West)),
+            Ordinal::NorthWest => Some((Cardinal::North, Cardinal::West)),
+            _ => None,
+        }
+    }
+
+    pub fn opposite(&self) -> Ordinal {
+        self.rotate_cw().rotate_cw().rotate_cw().rotate_cw()
+    }
+
+    /// Rotates 45 degrees clockwise
+    pub fn rotate_cw(&self) -> Ordinal {
+        Self::ALL[(self.index() + 1) % 8]
+    }
+
+    /// Rotates 45 degrees counter-clockwise
+    pub fn rotate_ccw(&self) -> Ordinal {
+        Self::ALL[(self.index() + 7) % 8]
+    }
+
+    /// The direction closest to the vector's angle, or `None` for the zero vector
+    /// (or one that isn't finite). Ties between two directions go clockwise.
+    pub fn nearest(v: Vec2) -> Option<Ordinal> {
+        if !v.is_finite() || v == Vec2::ZERO {
+            return None;
+        }
+        // comparing against the boundaries between directions exactly, rather than going through an
+        // angle, so that ties really do go clockwise. Clockwise turns from the Y axis towards the X axis
+        // where X and Y have the same sign, and from the X axis towards the Y axis where they don't
+        let (ax, ay) = (v.x.abs(), v.y.abs());
+        let same_sign = (v.x >= 0.) == (v.y >= 0.);
+        let near_y_axis = if same_sign { ax < TAN_PI_8 * ay } else { ax <= TAN_PI_8 * ay };
+        let near_x_axis = if same_sign { ay <= TAN_PI_8 * ax } else { ay < TAN_PI_8 * ax };
+        let ordinal = if near_y_axis {
+            if v.y > 0. { Ordinal::North } else { Ordinal::South }
+        } else if near_x_axis {
+            if v.x > 0. { Ordinal::East } else { Ordinal::West }
+        } else {
+            match (v.x > 0., v.y > 0.) {
+                (true, true) => Ordinal::NorthEast,
+                (true, false) => Ordinal::SouthEast,
+                (false, false) => Ordinal::SouthWest,
+                (false, true) => Ordinal::NorthWest,
+            }
+        };
+        Some(ordinal)
+    }
+
+    fn index(&self) -> usize {
+        Self::ALL.iter().position(|o| o == self).unwrap()
+    }
+}
+
+impl From<Cardinal> for Ordinal {
+    fn from(cardinal: Cardinal) -> Self {
+        match cardinal {
+            Cardinal::North => Ordinal::North,
+            Cardinal::East => Ordinal::East,
+            Cardinal::South => Ordinal::South,
+            Cardinal::West => Ordinal::West,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors_convert_to_the_axis_they_mostly_point_along() {
+        let cases = [
+            ((2., -1.), Cardinal::East),
+            ((0.5, -3.), Cardinal::South),
+            ((-0., 1.), Cardinal::North),
+            ((-1., 0.99), Cardinal::West),
+            ((1e-30, 0.), Cardinal::East),
+        ];
+        for ((x, y), cardinal) in cases {
+            assert_eq!(Cardinal::try_from(Vec2::new(x, y)).ok(), Some(cardinal), "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn zero_diagonal_and_non_finite_vectors_are_not_cardinal() {
+        let cases = [
+            (0., 0.),
+            (-0., 0.),
+            (1., 1.),
+            (-2., 2.),
+            (3., -3.),
+            (f32::NAN, 1.),
+            (0., f32::NAN),
+            (f32::INFINITY, 0.),
+            (1., f32::NEG_INFINITY),
+        ];
+        for (x, y) in cases {
+            assert!(Cardinal::try_from(Vec2::new(x, y)).is_err(), "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn nearest_ordinal_rounds_to_the_closest_eighth_of_a_turn() {
+        for ordinal in Ordinal::iter() {
+            let (x, y) = ordinal.offset();
+            assert_eq!(Ordinal::nearest(Vec2::new(x as f32, y as f32) * 3.), Some(ordinal));
+            // a little to either side of it
+            let v = ordinal.as_vec2();
+            assert_eq!(Ordinal::nearest(v + v.perp() * 0.3), Some(ordinal));
+            assert_eq!(Ordinal::nearest(v - v.perp() * 0.3), Some(ordinal));
+        }
+        // either sign of zero along the other axis
+        assert_eq!(Ordinal::nearest(Vec2::new(-0., -1.)), Some(Ordinal::South));
+        assert_eq!(Ordinal::nearest(Vec2::new(0., -1.)), Some(Ordinal::South));
+        assert_eq!(Ordinal::nearest(Vec2::new(-1., -0.)), Some(Ordinal::West));
+    }
+
+    #[test]
+    fn nearest_ordinal_breaks_ties_clockwise() {
+        // exactly on the boundary between each direction and the next one clockwise
+        let ties = [
+            ((TAN_PI_8, 1.), Ordinal::NorthEast),
+            ((1., TAN_PI_8), Ordinal::East),
+            ((1., -TAN_PI_8), Ordinal::SouthEast),
+            ((TAN_PI_8, -1.), Ordinal::South),
+            ((-TAN_PI_8, -1.), Ordinal::SouthWest),
+            ((-1., -TAN_PI_8), Ordinal::West),
+            ((-1., TAN_PI_8), Ordinal::NorthWest),
+            ((-TAN_PI_8, 1.), Ordinal::North),
+        ];
+        for ((x, y), ordinal) in ties {
+            assert_eq!(Ordinal::nearest(Vec2::new(x, y) * 4.), Some(ordinal), "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn zero_and_non_finite_vectors_have_no_nearest_ordinal() {
+        for (x, y) in [(0., 0.), (-0., -0.), (f32::NAN, 1.), (1., f32::NAN), (f32::INFINITY, 1.)] {
+            assert_eq!(Ordinal::nearest(Vec2::new(x, y)), None, "({x}, {y})");
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::ops::{Add, Index, IndexMut};
+
+use bevy::prelude::{Component, Resource};
+
+use crate::fill::{ReadTiles, Tiles};
+use crate::grid::{Grid, TileAddress};
+
+/// Address of a tile in an unbounded grid. Unlike `TileAddress`, coordinates may be negative.
+#[derive(Component, Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct SignedTileAddress(pub i32, pub i32);
+
+impl SignedTileAddress {
+    pub fn as_tuple(&self) -> (i32, i32) {
+        (self.0, self.1)
+    }
+}
+
+impl Add<(i32, i32)> for SignedTileAddress {
+    type Output = SignedTileAddress;
+
+    fn add(self, (dx, dy): (i32, i32)) -> Self::Output {
+        SignedTileAddress(self.0 + dx, self.1 + dy)
+    }
+}
+
+impl TryFrom<TileAddress> for SignedTileAddress {
+    type Error = std::num::TryFromIntError;
+
+    fn try_from(addr: TileAddress) -> Result<Self, Self::Error> {
+        Ok(SignedTileAddress(i32::try_from(addr.0)?, i32::try_from(addr.1)?))
+    }
+}
+
+impl TryFrom<SignedTileAddress> for TileAddress {
+    type Error = std::num::TryFromIntError;
+
+    fn try_from(addr: SignedTileAddress) -> Result<Self, Self::Error> {
+        Ok(TileAddress(u32::try_from(addr.0)?, u32::try_from(addr.1)?))
+    }
+}
+
+/// Address of a chunk within a `ChunkedGrid`, counted in chunks rather than tiles.
+#[derive(Component, Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct ChunkAddress(pub i32, pub i32);
+
+/// An unbounded grid, split into square chunks of `chunk_size × chunk_size` tiles.
+/// Chunks are allocated lazily; tiles in chunks that haven't been loaded read as `None`.
+#[derive(Clone, Resource)]
+pub struct ChunkedGrid<T> {
+    chunk_size: u32,
+    chunks: HashMap<ChunkAddress, Grid<T>>,
+}
+
+impl<T> ChunkedGrid<T> {
+    pub fn new(chunk_size: u32) -> Self {
+        assert!(chunk_size > 0 && chunk_size <= i32::MAX as u32, "chunk_size out of range");
+        ChunkedGrid {
+            chunk_size,
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn chunk_size(&self) -> u32 { self.chunk_size }
+
+    /// Splits a tile address into the chunk that contains it and its position within that chunk.
+    pub fn locate(&self, addr: &SignedTileAddress) -> (ChunkAddress, TileAddress) {
+        let size = self.chunk_size as i32;
+        let chunk = ChunkAddress(addr.0.div_euclid(size), addr.1.div_euclid(size));
+        let local = TileAddress(addr.0.rem_euclid(size) as u32, addr.1.rem_euclid(size) as u32);
+        (chunk, local)
+    }
+
+    /// Address of the bottom-left tile of the given chunk.
+    pub fn chunk_origin(&self, chunk: &ChunkAddress) -> SignedTileAddress {
+        let size = self.chunk_size as i32;
+        SignedTileAddress(chunk.0 * size, chunk.1 * size)
+    }
+
+    pub fn is_loaded(&self, chunk: &ChunkAddress) -> bool {
+        self.chunks.contains_key(chunk)
+    }
+
+    pub fn chunk(&self, chunk: &ChunkAddress) -> Option<&Grid<T>> {
+        self.chunks.get(chunk)
+    }
+
+    pub fn chunk_mut(&mut self, chunk: &ChunkAddress) -> Option<&mut Grid<T>> {
+        self.chunks.get_mut(chunk)
+    }
+
+    /// Inserts a chunk's worth of tiles, returning the chunk it replaced, if any.
+    pub fn insert_chunk(&mut self, chunk: ChunkAddress, tiles: Grid<T>) -> Option<Grid<T>> {
+        assert!(
+            tiles.width() == self.chunk_size && tiles.height() == self.chunk_size,
+            "chunk tiles must be chunk_size × chunk_size"
+        );
+        self.chunks.insert(chunk, tiles)
+    }
+
+    pub fn unload_chunk(&mut self, chunk: &ChunkAddress) -> Option<Grid<T>> {
+        self.chunks.remove(chunk)
+    }
+
+    pub fn loaded_chunks(&self) -> impl Iterator<Item=(&ChunkAddress, &Grid<T>)> {
+        self.chunks.iter()
+    }
+
+    pub fn loaded_chunks_mut(&mut self) -> impl Iterator<Item=(&ChunkAddress, &mut Grid<T>)> {
+        self.chunks.iter_mut()
+    }
+
+    /// Addresses of every tile in every loaded chunk, chunk by chunk.
+    pub fn addresses(&self) -> impl Iterator<Item=SignedTileAddress> + '_ {
+        self.chunks.keys().flat_map(move |chunk| {
+            let origin = self.chunk_origin(chunk);
+            let size = self.chunk_size as i32;
+            (0..size).flat_map(move |y| {
+                (0..size).map(move |x| origin + (x, y))
+            })
+        })
+    }
+
+    pub fn tile_at(&self, addr: &SignedTileAddress) -> Option<&T> {
+        let (chunk, local) = self.locate(addr);
+        self.chunks.get(&chunk).and_then(|tiles| tiles.tile_at(&local))
+    }
+
+    pub fn tile_at_mut(&mut self, addr: &SignedTileAddress) -> Option<&mut T> {
+        let (chunk, local) = self.locate(addr);
+        self.chunks.get_mut(&chunk).and_then(|tiles| tiles.tile_at_mut(&local))
+    }
+}
+
+impl<T: Default + Clone> ChunkedGrid<T> {
+    /// Returns the given chunk, allocating it filled with default tiles if it wasn't loaded.
+    pub fn chunk_or_default(&mut self, chunk: ChunkAddress) -> &mut Grid<T> {
+        let size = self.chunk_size;
+        self.chunks.entry(chunk).or_insert_with(|| Grid::new(size, size))
+    }
+
+    /// Returns the tile at the given address, allocating its chunk if needed.
+    pub fn tile_or_default(&mut self, addr: &SignedTileAddress) -> &mut T {
+        let (chunk, local) = self.locate(addr);
+        &mut self.chunk_or_default(chunk)[local]
+    }
+}
+
+impl<T, A: Borrow<SignedTileAddress>> Index<A> for ChunkedGrid<T> {
+    type Output = T;
+
+    fn index(&self, idx: A) -> &Self::Output {
+        self.tile_at(idx.borrow()).expect("chunk not loaded")
+    }
+}
+
+impl<T, A: Borrow<SignedTileAddress>> IndexMut<A> for ChunkedGrid<T> {
+    fn index_mut(&mut self, idx: A) -> &mut Self::Output {
+        self.tile_at_mut(idx.borrow()).expect("chunk not loaded")
+    }
+}
+
+impl<T> ReadTiles<i32> for ChunkedGrid<T>
+    where T: Sized + PartialEq
+{
+    type Tile = T;
+    fn get_tile(&self, x: i32, y: i32) -> Option<&T> {
+        self.tile_at(&SignedTileAddress(x, y))
+    }
+}
+
+impl<T> Tiles<i32> for ChunkedGrid<T>
+    where T: Sized + PartialEq + Default + Clone
+{
+    fn set_tile(&mut self, x: i32, y: i32, tile: T) {
+        *self.tile_or_default(&SignedTileAddress(x, y)) = tile;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fill::flood_fill;
+
+    use super::*;
+
+    const CHUNK: i32 = 16;
+
+    #[test]
+    fn addresses_split_into_chunk_and_local_position() {
+        let grid = ChunkedGrid::<u8>::new(CHUNK as u32);
+        let cases = [
+            (-1, -1, 15),
+            (0, 0, 0),
+            (CHUNK - 1, 0, 15),
+            (CHUNK, 1, 0),
+            (-CHUNK, -1, 0),
+            (-CHUNK - 1, -2, 15),
+            (i32::MIN, i32::MIN / CHUNK, 0),
+            (i32::MAX, i32::MAX / CHUNK, 15),
+        ];
+        for (tile, chunk, local) in cases {
+            assert_eq!(grid.locate(&SignedTileAddress(tile, 3)), (ChunkAddress(chunk, 0), TileAddress(local, 3)), "x = {tile}");
+            assert_eq!(grid.locate(&SignedTileAddress(3, tile)), (ChunkAddress(0, chunk), TileAddress(3, local)), "y = {tile}");
+        }
+    }
+
+    #[test]
+    fn chunk_origins_and_local_positions_add_back_up() {
+        let grid = ChunkedGrid::<u8>::new(CHUNK as u32);
+        for y in -2 * CHUNK - 1..=2 * CHUNK + 1 {
+            for x in [-CHUNK - 1, -CHUNK, -1, 0, 1, CHUNK - 1, CHUNK, 3 * CHUNK] {
+                let addr = SignedTileAddress(x, y);
+                let (chunk, local) = grid.locate(&addr);
+                assert_eq!(grid.chunk_origin(&chunk) + (local.0 as i32, local.1 as i32), addr);
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_set_across_chunks_read_back() {
+        let mut grid = ChunkedGrid::<i32>::new(CHUNK as u32);
+        let addrs: Vec<SignedTileAddress> = [-CHUNK - 1, -CHUNK, -1, 0, CHUNK - 1, CHUNK]
+            .into_iter()
+            .flat_map(|x| [-1, 0, CHUNK].map(|y| SignedTileAddress(x, y)))
+            .collect();
+        for addr in &addrs {
+            grid.set_tile(addr.0, addr.1, addr.0 * 1000 + addr.1);
+        }
+        for addr in &addrs {
+            assert_eq!(grid[addr], addr.0 * 1000 + addr.1);
+        }
+        // every distinct chunk those land in was allocated, and nothing else
+        assert_eq!(grid.loaded_chunks().count(), 4 * 3);
+        assert_eq!(grid.addresses().count(), 12 * (CHUNK * CHUNK) as usize);
+        assert_eq!(grid.tile_at(&SignedTileAddress(-1, -1)), Some(&-1001));
+        assert_eq!(grid.tile_at(&SignedTileAddress(-1, 1)), Some(&0));
+        assert_eq!(grid.tile_at(&SignedTileAddress(2 * CHUNK, 0)), None);
+
+        let (chunk, _) = grid.locate(&SignedTileAddress(-1, -1));
+        assert!(grid.unload_chunk(&chunk).is_some());
+        assert_eq!(grid.get_tile(-1, -1), None);
+        assert_eq!(grid.get_tile(0, -1), Some(&-1));
+    }
+
+    #[test]
+    fn flood_fill_crosses_chunk_boundaries() {
+        let mut grid = ChunkedGrid::<u8>::new(4);
+        for chunk in [ChunkAddress(-1, -1), ChunkAddress(0, -1), ChunkAddress(-1, 0), ChunkAddress(0, 0)] {
+            grid.chunk_or_default(chunk);
+        }
+        flood_fill(&mut grid, (0, 0), |a: &u8, b: &u8| a == b, 1);
+        assert!(grid.addresses().all(|addr| grid[addr] == 1));
+        assert_eq!(grid.loaded_chunks().count(), 4);
+    }
+}
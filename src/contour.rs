@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Vec2;
+
+use crate::border::collect_unclassified_borders;
+use crate::cardinal::Cardinal;
+use crate::fill::ReadTiles;
+use crate::grid::Bounded;
+use crate::zone::GridDimensions;
+
+/// A tile corner, in tiles. Corner `(x, y)` is the bottom-left corner of tile `(x, y)`.
+pub type Corner = (u32, u32);
+
+/// A closed outline around part of a region, as traced by `trace_contours`.
+#[derive(Clone, Debug)]
+pub struct Contour {
+    /// Corners where the outline changes direction, in order. The last corner connects back to the first.
+    pub corners: Vec<Corner>,
+    /// Outer boundaries wind counter-clockwise (inside on the left);
+    /// the boundaries of holes within a region wind clockwise.
+    pub is_hole: bool,
+}
+
+impl Contour {
+    pub fn world_points(&self, dims: &GridDimensions) -> Vec<Vec2> {
+        self.corners
+            .iter()
+            .map(|&(x, y)| dims.bottom_left + Vec2::new(x as f32, y as f32) * dims.tile_size)
+            .collect()
+    }
+
+    /// Index pairs for the contour's edges, including the one that closes the loop,
+    /// in the form `Collider::polyline` wants them
+    pub fn closed_indices(&self) -> Vec<[u32; 2]> {
+        let n = self.corners.len() as u32;
+        (0..n).map(|i| [i, (i + 1) % n]).collect()
+    }
+}
+
+/// Traces the closed outlines of every region of inside tiles, including the outlines of any holes.
+///
+/// Regions that only touch at a corner are traced separately, same as `flood_fill` would find them.
+pub fn trace_contours<G, F>(grid: &G, test_inside: &F) -> Vec<Contour>
+    where G: ReadTiles<u32> + Bounded,
+          F: Fn(&G::Tile) -> bool
+{
+    // every border becomes a directed edge with the inside on its left
+    let mut outgoing: HashMap<Corner, Vec<Corner>> = HashMap::new();
+    // contours don't care what kind of border they follow, so there's no need to tell lakes apart
+    collect_unclassified_borders(grid, test_inside, &mut |border| {
+        let (x, y) = border.pos().as_tuple();
+        let (from, to) = match border.facing() {
+            Cardinal::East => ((x, y), (x, y + 1)),
+            Cardinal::West => ((x, y + 1), (x, y)),
+            Cardinal::North => ((x + 1, y), (x, y)),
+            Cardinal::South => ((x, y), (x + 1, y)),
+        };
+        outgoing.entry(from).or_default().push(to);
+    });
+
+    let mut contours = Vec::new();
+    // every loop has corners that only it passes through; starting from one of those means
+    // the first time we get back to it, the loop is done
+    while let Some(start) = outgoing.iter().find(|(_, edges)| edges.len() == 1).map(|(corner, _)| *corner) {
+        let mut path = vec![start];
+        let mut from = start;
+        let mut to = take_edge(&mut outgoing, start, None);
+        while to != start {
+            path.push(to);
+            let next = take_edge(&mut outgoing, to, Some(direction(from, to)));
+            from = to;
+            to = next;
+        }
+        contours.push(simplify(path));
+    }
+    contours
+}
+
+/// Removes and returns the next edge out of `corner`. Where two regions meet at a corner,
+/// turning left keeps following the region we came from, rather than crossing into the other.
+fn take_edge(outgoing: &mut HashMap<Corner, Vec<Corner>>, corner: Corner, heading: Option<(i32, i32)>) -> Corner {
+    let edges = outgoing.get_mut(&corner).expect("borders always form closed loops");
+    let index = match heading {
+        Some(heading) => (0..edges.len())
+            .max_by_key(|&i| turn_preference(heading, direction(corner, edges[i])))
+            .unwrap(),
+        None => 0,
+    };
+    let to = edges.swap_remove(index);
+    if edges.is_empty() {
+        outgoing.remove(&corner);
+    }
+    to
+}
+
+fn direction(from: Corner, to: Corner) -> (i32, i32) {
+    (to.0 as i32 - from.0 as i32, to.1 as i32 - from.1 as i32)
+}
+
+// left turns first, then straight on, then right turns
+fn turn_preference(heading: (i32, i32), next: (i32, i32)) -> i32 {
+    let cross = heading.0 * next.1 - heading.1 * next.0;
+    let dot = heading.0 * next.0 + heading.1 * next.1;
+    if cross > 0 { 2 } else if dot > 0 { 1 } else { 0 }
+}
+
+// drops the corners along straight runs, and works out which way the loop winds
+fn simplify(path: Vec<Corner>) -> Contour {
+    let n = path.len();
+    let mut corners = Vec::new();
+    let mut twice_area = 0i64;
+    for i in 0..n {
+        let prev = path[(i + n - 1) % n];
+        let here = path[i];
+        let next = path[(i + 1) % n];
+        if direction(prev, here) != direction(here, next) {
+            corners.push(here);
+        }
+        twice_area += here.0 as i64 * next.1 as i64 - next.0 as i64 * here.1 as i64;
+    }
+    Contour {
+        corners,
+        is_hole: twice_area < 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::grid::{Grid, TileAddress};
+
+    use super::*;
+
+    fn contours_of(width: u32, height: u32, inside: impl Fn(u32, u32) -> bool) -> Vec<Contour> {
+        let grid = Grid::from_fn(width, height, |TileAddress(x, y)| inside(x, y));
+        trace_contours(&grid, &|tile: &bool| *tile)
+    }
+
+    // twice the signed area, positive for counter-clockwise loops; also checks that the loop is
+    // closed, with only right-angled turns between its corners
+    fn checked_twice_area(contour: &Contour) -> i64 {
+        let corners = &contour.corners;
+        let n = corners.len();
+        assert!(n >= 4, "{corners:?}");
+        assert_eq!(corners.iter().collect::<HashSet<_>>().len(), n, "{corners:?}");
+        let mut twice_area = 0;
+        for i in 0..n {
+            let (prev, here, next) = (corners[(i + n - 1) % n], corners[i], corners[(i + 1) % n]);
+            let (a, b) = (direction(prev, here), direction(here, next));
+            assert!(a.0 == 0 || a.1 == 0, "{corners:?}");
+            assert_eq!(a.0 * b.0 + a.1 * b.1, 0, "{corners:?}");
+            twice_area += here.0 as i64 * next.1 as i64 - next.0 as i64 * here.1 as i64;
+        }
+        twice_area
+    }
+
+    fn corner_set(contour: &Contour) -> HashSet<Corner> {
+        contour.corners.iter().copied().collect()
+    }
+
+    #[test]
+    fn a_single_tile_is_a_counter_clockwise_square() {
+        let contours = contours_of(4, 3, |x, y| (x, y) == (2, 1));
+        assert_eq!(contours.len(), 1);
+        assert!(!contours[0].is_hole);
+        assert_eq!(checked_twice_area(&contours[0]), 2);
+        assert_eq!(corner_set(&contours[0]), HashSet::from([(2, 1), (3, 1), (3, 2), (2, 2)]));
+        assert_eq!(contours[0].closed_indices(), [[0, 1], [1, 2], [2, 3], [3, 0]]);
+    }
+
+    #[test]
+    fn holes_wind_clockwise() {
+        let contours = contours_of(5, 5, |x, y| (1..=3).contains(&x) && (1..=3).contains(&y) && (x, y) != (2, 2));
+        assert_eq!(contours.len(), 2);
+        let (holes, outlines): (Vec<_>, Vec<_>) = contours.iter().partition(|contour| contour.is_hole);
+        assert_eq!(checked_twice_area(outlines[0]), 18);
+        assert_eq!(corner_set(outlines[0]), HashSet::from([(1, 1), (4, 1), (4, 4), (1, 4)]));
+        assert_eq!(checked_twice_area(holes[0]), -2);
+        assert_eq!(corner_set(holes[0]), HashSet::from([(2, 2), (3, 2), (3, 3), (2, 3)]));
+    }
+
+    #[test]
+    fn shapes_touching_the_edge_of_the_grid_are_closed_along_it() {
+        let contours = contours_of(3, 2, |_, _| true);
+        assert_eq!(contours.len(), 1);
+        assert_eq!(checked_twice_area(&contours[0]), 12);
+        assert_eq!(corner_set(&contours[0]), HashSet::from([(0, 0), (3, 0), (3, 2), (0, 2)]));
+
+        // an L in the corner of the grid
+        let contours = contours_of(3, 3, |x, y| x == 0 || y == 0);
+        assert_eq!(contours.len(), 1);
+        assert!(!contours[0].is_hole);
+        assert_eq!(checked_twice_area(&contours[0]), 10);
+        assert_eq!(contours[0].corners.len(), 6);
+    }
+
+    #[test]
+    fn regions_touching_at_a_corner_are_traced_separately() {
+        let contours = contours_of(2, 2, |x, y| x == y);
+        assert_eq!(contours.len(), 2);
+        for contour in &contours {
+            assert!(!contour.is_hole);
+            assert_eq!(checked_twice_area(contour), 2);
+        }
+    }
+}
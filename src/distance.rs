@@ -0,0 +1,215 @@
+//! Distance transforms: for every tile, how far it is to the nearest tile that fails a predicate,
+//! e.g. how far each floor tile is from the water.
+//!
+//! Distances are between tile centers, in tiles. Tiles that fail the predicate are 0, and if no tile
+//! fails it, every tile is `f32::INFINITY`. Beyond the edges of the grid doesn't count as failing.
+
+use crate::fill::ReadTiles;
+use crate::grid::{Bounded, Grid, TileAddress};
+
+// stands in for infinity in the exact transform, where real infinities would turn into NaNs
+const FAR: f64 = 1e20;
+
+/// The exact Euclidean distance from each tile to the nearest tile that fails the `predicate`.
+pub fn distance_transform<G, P>(grid: &G, predicate: P) -> Grid<f32>
+    where G: ReadTiles<u32> + Bounded,
+          P: Fn(&G::Tile) -> bool
+{
+    let bounds = grid.bounds();
+    let (width, height) = (bounds.width as usize, bounds.height as usize);
+    let mut squared: Vec<f64> = bounds.addresses()
+        .map(|addr| if grid.get_tile(addr.0, addr.1).is_some_and(&predicate) { FAR } else { 0.0 })
+        .collect();
+
+    // squared distances along each column, then combined along each row (Felzenszwalb & Huttenlocher)
+    let mut scratch = Scratch::new(width.max(height));
+    let mut line = vec![0.0; height];
+    for x in 0..width {
+        for y in 0..height {
+            line[y] = squared[y * width + x];
+        }
+        scratch.transform(&mut line);
+        for y in 0..height {
+            squared[y * width + x] = line[y];
+        }
+    }
+    for row in squared.chunks_mut(width.max(1)) {
+        scratch.transform(row);
+    }
+
+    Grid::from_fn(bounds.width, bounds.height, |TileAddress(x, y)| {
+        let d = squared[y as usize * width + x as usize];
+        if d >= FAR { f32::INFINITY } else { d.sqrt() as f32 }
+    })
+}
+
+/// An approximate distance transform, in two quick sweeps over the grid with 3-4 chamfer weights:
+/// a step to a straight neighbor costs 3, and to a diagonal one 4, in thirds of a tile. That's exact
+/// along rows and columns, and otherwise within 6% of the exact Euclidean distance (up to 5.4% over
+/// and 5.7% under).
+pub fn chamfer_distance_transform<G, P>(grid: &G, predicate: P) -> Grid<f32>
+    where G: ReadTiles<u32> + Bounded,
+          P: Fn(&G::Tile) -> bool
+{
+    const STRAIGHT: u32 = 3;
+    const DIAGONAL: u32 = 4;
+
+    let bounds = grid.bounds();
+    let (width, height) = (bounds.width as i64, bounds.height as i64);
+    let mut thirds: Vec<u32> = bounds.addresses()
+        .map(|addr| if grid.get_tile(addr.0, addr.1).is_some_and(&predicate) { u32::MAX } else { 0 })
+        .collect();
+
+    // the neighbors that come before a tile in row order, then the ones after it
+    let before = [(-1, 0, STRAIGHT), (-1, -1, DIAGONAL), (0, -1, STRAIGHT), (1, -1, DIAGONAL)];
+    let after = before.map(|(dx, dy, cost)| (-dx, -dy, cost));
+    let mut relax = |x: i64, y: i64, neighbors: &[(i64, i64, u32); 4]| {
+        let index = (y * width + x) as usize;
+        for &(dx, dy, cost) in neighbors {
+            let (nx, ny) = (x + dx, y + dy);
+            if (0..width).contains(&nx) && (0..height).contains(&ny) {
+                let through = thirds[(ny * width + nx) as usize].saturating_add(cost);
+                thirds[index] = thirds[index].min(through);
+            }
+        }
+    };
+    for y in 0..height {
+        for x in 0..width {
+            relax(x, y, &before);
+        }
+    }
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            relax(x, y, &after);
+        }
+    }
+
+    Grid::from_fn(bounds.width, bounds.height, |TileAddress(x, y)| {
+        let d = thirds[y as usize * width as usize + x as usize];
+        if d == u32::MAX { f32::INFINITY } else { d as f32 / STRAIGHT as f32 }
+    })
+}
+
+// buffers for the one-dimensional transform, reused from line to line
+struct Scratch {
+    parabolas: Vec<usize>,
+    boundaries: Vec<f64>,
+    result: Vec<f64>,
+}
+
+impl Scratch {
+    fn new(len: usize) -> Self {
+        Scratch {
+            parabolas: vec![0; len],
+            boundaries: vec![0.0; len + 1],
+            result: vec![0.0; len],
+        }
+    }
+
+    // replaces each value with the minimum of (distance to q)² + value at q, over every q in the line,
+    // by finding the lower envelope of the parabolas rooted at each q
+    fn transform(&mut self, line: &mut [f64]) {
+        let n = line.len();
+        if n == 0 {
+            return;
+        }
+        let (v, z) = (&mut self.parabolas, &mut self.boundaries);
+        let mut k = 0;
+        v[0] = 0;
+        z[0] = f64::NEG_INFINITY;
+        z[1] = f64::INFINITY;
+        for q in 1..n {
+            let intersect = |p: usize| {
+                let (qf, pf) = (q as f64, p as f64);
+                ((line[q] + qf * qf) - (line[p] + pf * pf)) / (2.0 * qf - 2.0 * pf)
+            };
+            let mut s = intersect(v[k]);
+            while s <= z[k] {
+                k -= 1;
+                s = intersect(v[k]);
+            }
+            k += 1;
+            v[k] = q;
+            z[k] = s;
+            z[k + 1] = f64::INFINITY;
+        }
+        k = 0;
+        for q in 0..n {
+            while z[k + 1] < q as f64 {
+                k += 1;
+            }
+            let offset = q as f64 - v[k] as f64;
+            self.result[q] = offset * offset + line[v[k]];
+        }
+        line.copy_from_slice(&self.result[..n]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::grid::Distance;
+
+    use super::*;
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for (width, height) in [(9, 6), (1, 7), (12, 1)] {
+            let mask = Grid::from_fn(width, height, |_| rng.gen_bool(0.8));
+            let failing: Vec<TileAddress> = mask.addresses().filter(|&addr| !mask[addr]).collect();
+            let distances = distance_transform(&mask, |tile| *tile);
+            for addr in mask.addresses() {
+                let expected = failing.iter()
+                    .map(|&other| Distance::Euclidean.between(addr, other))
+                    .fold(f32::INFINITY, f32::min);
+                assert!((distances[addr] - expected).abs() < 1e-4, "{:?}: {} vs {}", addr, distances[addr], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn chamfer_stays_within_its_error_bound() {
+        // the 3-4 chamfer distance over the exact one is somewhere from 2√2/3 (on the diagonals)
+        // to √10/3 (a third of the way from a row or column towards a diagonal)
+        let (low, high) = (2.0 * std::f32::consts::SQRT_2 / 3.0, 10f32.sqrt() / 3.0);
+        let mut rng = StdRng::seed_from_u64(7);
+        for (width, height, density) in [(30, 20, 0.9), (25, 25, 0.99), (1, 9, 0.7), (40, 3, 0.95)] {
+            let mask = Grid::from_fn(width, height, |_| rng.gen_bool(density));
+            let exact = distance_transform(&mask, |tile| *tile);
+            let chamfer = chamfer_distance_transform(&mask, |tile| *tile);
+            for addr in mask.addresses() {
+                let (e, c) = (exact[addr], chamfer[addr]);
+                if e.is_infinite() {
+                    assert!(c.is_infinite(), "{:?}: {} vs {}", addr, c, e);
+                } else {
+                    assert!(c >= e * low - 1e-4 && c <= e * high + 1e-4, "{:?}: {} vs {}", addr, c, e);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chamfer_is_exact_along_rows_and_columns() {
+        let mask = Grid::from_fn(9, 9, |addr| addr != TileAddress(4, 4));
+        let chamfer = chamfer_distance_transform(&mask, |tile| *tile);
+        for d in 0..=4 {
+            assert_eq!(chamfer[TileAddress(4 + d, 4)], d as f32);
+            assert_eq!(chamfer[TileAddress(4, 4 - d)], d as f32);
+        }
+        // and a diagonal step counts as 4/3 of a tile
+        assert_eq!(chamfer[TileAddress(7, 7)], 4.0);
+    }
+
+    #[test]
+    fn everything_is_infinitely_far_when_nothing_fails() {
+        for transform in [distance_transform::<Grid<bool>, fn(&bool) -> bool>, chamfer_distance_transform] {
+            let distances = transform(&Grid::from_fn(5, 4, |_| true), |tile| *tile);
+            assert!(distances.addresses().all(|addr| distances[addr] == f32::INFINITY));
+
+            let distances = transform(&Grid::from_fn(5, 4, |_| false), |tile| *tile);
+            assert!(distances.addresses().all(|addr| distances[addr] == 0.0));
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use bevy::prelude::*;
+
+use crate::grid::TileAddress;
+use crate::input::PlayerCursor;
+use crate::zone::{GridDimensions, TileState, ZoneCommand, ZoneSystems};
+
+/// In-game tile painting. Press Tab to switch between playing and editing.
+///
+/// While editing, the mouse paints instead of firing lasers or moving the player:
+///  - `1`/`2` pick floor or water
+///  - `B` brush, `L` line, `R` rectangle, `F` bucket fill
+///  - `Q` toggles between a square and circle brush, `[`/`]` shrink or grow it
+///  - `Ctrl+Z` undoes, `Ctrl+Y` (or `Ctrl+Shift+Z`) redoes
+///
+/// Every edit goes through `ZoneCommand`s, so edits can be undone and get saved with the zone.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_state::<EditorState>()
+            .insert_resource(Brush::default())
+            .add_systems(Update, toggle_editor)
+            .add_systems(OnExit(EditorState::Editing), end_stroke)
+            .add_systems(Update, (editor_hotkeys, paint_with_mouse)
+                .chain()
+                .run_if(in_state(EditorState::Editing))
+                .before(ZoneSystems::Commands))
+        ;
+    }
+}
+
+#[derive(States, Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum EditorState {
+    #[default]
+    Playing,
+    Editing,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BrushShape {
+    Square,
+    Circle,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EditorTool {
+    /// Paints under the cursor while the button is held
+    Brush,
+    /// Paints a line, with the brush, from where the button was pressed to where it was released
+    Line,
+    /// Fills the rectangle between where the button was pressed and where it was released
+    Rect,
+    /// Flood fills the region under the cursor
+    Bucket,
+}
+
+#[derive(Resource, Debug)]
+pub struct Brush {
+    pub tile: TileState,
+    pub tool: EditorTool,
+    pub shape: BrushShape,
+    /// Distance in tiles from the center of the brush to its edge; 0 paints a single tile
+    pub radius: u32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Brush {
+            tile: TileState::Floor,
+            tool: EditorTool::Brush,
+            shape: BrushShape::Square,
+            radius: 0,
+        }
+    }
+}
+
+impl Brush {
+    const MAX_RADIUS: u32 = 16;
+
+    /// Tiles covered by the brush when centered on `center`. May include out-of-bounds tiles,
+    /// which the zone ignores.
+    fn stamp(&self, center: TileAddress, out: &mut Vec<TileAddress>) {
+        let r = self.radius as i32;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let inside = match self.shape {
+                    BrushShape::Square => true,
+                    // the extra `r` rounds off the corners less aggressively than a strict distance check
+                    BrushShape::Circle => dx * dx + dy * dy <= r * r + r,
+                };
+                if inside {
+                    out.extend(center + (dx, dy));
+                }
+            }
+        }
+    }
+}
+
+fn toggle_editor(
+    keyboard: Res<Input<KeyCode>>,
+    state: Res<State<EditorState>>,
+    mut next_state: ResMut<NextState<EditorState>>,
+) {
+    if keyboard.just_pressed(KeyCode::Tab) {
+        next_state.set(match state.get() {
+            EditorState::Playing => EditorState::Editing,
+            EditorState::Editing => EditorState::Playing,
+        });
+    }
+}
+
+fn editor_hotkeys(
+    keyboard: Res<Input<KeyCode>>,
+    mut brush: ResMut<Brush>,
+    mut zone_commands: EventWriter<ZoneCommand>,
+) {
+    let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if ctrl {
+        if keyboard.just_pressed(KeyCode::Y) || (shift && keyboard.just_pressed(KeyCode::Z)) {
+            zone_commands.send(ZoneCommand::Redo);
+        } else if keyboard.just_pressed(KeyCode::Z) {
+            zone_commands.send(ZoneCommand::Undo);
+        }
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Key1) {
+        brush.tile = TileState::Floor;
+    }
+    if keyboard.just_pressed(KeyCode::Key2) {
+        brush.tile = TileState::Water;
+    }
+    if keyboard.just_pressed(KeyCode::B) {
+        brush.tool = EditorTool::Brush;
+    }
+    if keyboard.just_pressed(KeyCode::L) {
+        brush.tool = EditorTool::Line;
+    }
+    if keyboard.just_pressed(KeyCode::R) {
+        brush.tool = EditorTool::Rect;
+    }
+    if keyboard.just_pressed(KeyCode::F) {
+        brush.tool = EditorTool::Bucket;
+    }
+    if keyboard.just_pressed(KeyCode::Q) {
+        brush.shape = match brush.shape {
+            BrushShape::Square => BrushShape::Circle,
+            BrushShape::Circle => BrushShape::Square,
+        };
+    }
+    if keyboard.just_pressed(KeyCode::BracketLeft) {
+        brush.radius = brush.radius.saturating_sub(1);
+    }
+    if keyboard.just_pressed(KeyCode::BracketRight) {
+        brush.radius = (brush.radius + 1).min(Brush::MAX_RADIUS);
+    }
+}
+
+/// Where the current mouse stroke started, and the last tile it painted
+#[derive(Default)]
+struct Stroke {
+    start: Option<TileAddress>,
+    last: Option<TileAddress>,
+}
+
+fn paint_with_mouse(
+    buttons: Res<Input<MouseButton>>,
+    cursor: Res<PlayerCursor>,
+    dimensions: Res<GridDimensions>,
+    brush: Res<Brush>,
+    mut stroke: Local<Stroke>,
+    mut zone_commands: EventWriter<ZoneCommand>,
+) {
+    let hovered = dimensions.position_to_address(cursor.world_pos);
+
+    if buttons.just_pressed(MouseButton::Left) {
+        let Some(hovered) = hovered else { return };
+        *stroke = Stroke { start: Some(hovered), last: None };
+        match brush.tool {
+            EditorTool::Bucket => zone_commands.send(ZoneCommand::FloodFillAt(hovered, brush.tile)),
+            EditorTool::Brush => zone_commands.send(ZoneCommand::BeginEditGroup),
+            EditorTool::Line | EditorTool::Rect => {}
+        }
+    }
+
+    let Some(start) = stroke.start else { return };
+
+    if buttons.pressed(MouseButton::Left) && brush.tool == EditorTool::Brush {
+        if let Some(hovered) = hovered {
+            if stroke.last != Some(hovered) {
+                // fill in the gap when the cursor moves more than a tile in one frame
+                let from = stroke.last.unwrap_or(hovered);
+                let mut tiles = Vec::new();
+                for center in line_tiles(from, hovered) {
+                    brush.stamp(center, &mut tiles);
+                }
+                zone_commands.send(ZoneCommand::SetTiles(tiles, brush.tile));
+                stroke.last = Some(hovered);
+            }
+        }
+    }
+
+    if buttons.just_released(MouseButton::Left) {
+        let end = hovered.or(stroke.last).unwrap_or(start);
+        match brush.tool {
+            EditorTool::Brush => zone_commands.send(ZoneCommand::EndEditGroup),
+            EditorTool::Line => {
+                let mut tiles = Vec::new();
+                for center in line_tiles(start, end) {
+                    brush.stamp(center, &mut tiles);
+                }
+                zone_commands.send(ZoneCommand::SetTiles(tiles, brush.tile));
+            }
+            EditorTool::Rect => zone_commands.send(ZoneCommand::FillRect {
+                min: start,
+                max: end,
+                tile: brush.tile,
+            }),
+            EditorTool::Bucket => {}
+        }
+        *stroke = Stroke::default();
+    }
+}
+
+// leaving the editor mid-stroke shouldn't leave the history grouping edits forever
+fn end_stroke(mut zone_commands: EventWriter<ZoneCommand>) {
+    zone_commands.send(ZoneCommand::EndEditGroup);
+}
+
+/// Tiles along the line from `a` to `b` (inclusive), via Bresenham's algorithm.
+fn line_tiles(a: TileAddress, b: TileAddress) -> Vec<TileAddress> {
+    let (mut x, mut y) = (a.0 as i64, a.1 as i64);
+    let (x1, y1) = (b.0 as i64, b.1 as i64);
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let mut tiles = Vec::new();
+    loop {
+        tiles.push(TileAddress(x as u32, y as u32));
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+    tiles
+}
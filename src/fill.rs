@@ -1,12 +1,65 @@
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::iter::{Iterator, Step};
 use std::ops::{Not, RangeInclusive};
 
-pub trait Tiles<I> {
+/// Read access to a grid of tiles, which is all that queries like `query_region` need
+pub trait ReadTiles<I> {
     type Tile: Sized + PartialEq;
 
     fn get_tile(&self, x: I, y: I) -> Option<&Self::Tile>;
+
+    /// The tiles in row `y` from `xs.start()` to `xs.end()` as one slice, for implementations that
+    /// store rows contiguously. The slice stops short at the end of the row, and is empty if
+    /// `xs.start()` itself is out of bounds. `None` means callers have to fall back to `get_tile`.
+    fn row_slice(&self, _y: I, _xs: RangeInclusive<I>) -> Option<&[Self::Tile]> {
+        None
+    }
+}
+
+pub trait Tiles<I>: ReadTiles<I> {
     fn set_tile(&mut self, x: I, y: I, tile: Self::Tile);
+
+    /// Sets every tile in row `y` from `xs.start()` to `xs.end()`, skipping any that are out of bounds.
+    /// Implementations that store rows contiguously can override this to fill a slice instead.
+    fn fill_span(&mut self, y: I, xs: RangeInclusive<I>, tile: Self::Tile)
+        where I: Step + Copy,
+              Self::Tile: Clone
+    {
+        for x in xs {
+            self.set_tile(x, y, tile.clone());
+        }
+    }
+}
+
+/// Which neighbors of a tile count as connected to it
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum Connectivity {
+    /// Only the orthogonal neighbors; tiles that touch at a corner aren't connected
+    #[default]
+    Four,
+    /// The diagonal neighbors too
+    Eight,
+}
+
+/// Limits on how far `flood_fill_with` spreads
+#[derive(Copy, Clone, Debug)]
+pub struct FillOptions<I> {
+    pub connectivity: Connectivity,
+    /// Only tiles within this rectangle are filled, given as its `((min_x, min_y), (max_x, max_y))` corners (inclusive)
+    pub clip: Option<((I, I), (I, I))>,
+    /// The most tiles to fill; the fill stops once it's filled this many
+    pub budget: Option<usize>,
+}
+
+impl<I> Default for FillOptions<I> {
+    fn default() -> Self {
+        FillOptions {
+            connectivity: Connectivity::Four,
+            clip: None,
+            budget: None,
+        }
+    }
 }
 
 pub fn flood_fill<G, I, P, T>(
@@ -19,6 +72,297 @@ pub fn flood_fill<G, I, P, T>(
           T: Clone + Debug,
           I: Step + PartialOrd + Copy + Debug,
           P: Fn(&T, &T) -> bool,
+{
+    flood_fill_with(tiles, start, color_equivalence, color, &FillOptions::default());
+}
+
+/// `flood_fill`, with a choice of connectivity and limits on how far the fill can spread.
+/// Returns whether the fill stopped at its `budget` with tiles left unfilled. Once the budget
+/// runs out, the walk goes on only until it finds another tile to fill.
+pub fn flood_fill_with<G, I, P, T>(
+    tiles: &mut G,
+    start: (I, I),
+    color_equivalence: P,
+    color: T,
+    options: &FillOptions<I>,
+) -> bool
+    where G: Tiles<I, Tile=T>,
+          T: Clone + Debug,
+          I: Step + PartialOrd + Copy + Debug,
+          P: Fn(&T, &T) -> bool,
+{
+    let start_color: T = match tiles.get_tile(start.0, start.1) {
+        Some(t) => t.clone(),
+        None => return false,
+    };
+    let mut paint = Paint {
+        tiles,
+        tile_test: |c: &T| color_equivalence(c, &start_color),
+        color,
+        clip: options.clip,
+        remaining: options.budget,
+        refused: false,
+    };
+    scan_fill(&mut paint, start, options.connectivity);
+    paint.refused
+}
+
+/// Finds the region of tiles connected to `start` that pass the `tile_test`, using the same scanline
+/// walk as `flood_fill` but without changing anything. With a `max_size`, the walk stops as soon
+/// as it finds a tile that would take the region past that many tiles, and the region is marked
+/// as truncated.
+pub fn query_region<G, I, P, T>(
+    tiles: &G,
+    start: (I, I),
+    tile_test: P,
+    connectivity: Connectivity,
+    max_size: Option<usize>,
+) -> Region<I>
+    where G: ReadTiles<I, Tile=T>,
+          I: Step + Ord + Copy + Debug,
+          P: Fn(&T) -> bool,
+{
+    let mut query = Query {
+        tiles,
+        tile_test,
+        region: Region::default(),
+        max_size,
+    };
+    scan_fill(&mut query, start, connectivity);
+    query.region
+}
+
+/// A connected group of tiles, as found by `query_region`, stored as the spans it covers in each row.
+#[derive(Clone, Debug)]
+pub struct Region<I> {
+    rows: BTreeMap<I, Vec<Span<I>>>,
+    size: usize,
+    truncated: bool,
+}
+
+impl<I> Default for Region<I> {
+    fn default() -> Self {
+        Region {
+            rows: BTreeMap::new(),
+            size: 0,
+            truncated: false,
+        }
+    }
+}
+
+impl<I: Step + Ord + Copy> Region<I> {
+    /// How many tiles are in the region
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Whether the query stopped at its `max_size` before finding the whole region
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The smallest and largest X and Y of any tile in the region, as `((min_x, min_y), (max_x, max_y))`
+    pub fn bounds(&self) -> Option<((I, I), (I, I))> {
+        let min_y = *self.rows.keys().next()?;
+        let max_y = *self.rows.keys().next_back()?;
+        let min_x = self.rows.values().filter_map(|spans| spans.first()).map(|span| span.0).min()?;
+        let max_x = self.rows.values().filter_map(|spans| spans.last()).map(|span| span.1).max()?;
+        Some(((min_x, min_y), (max_x, max_y)))
+    }
+
+    /// Each row of the region, from the lowest Y, with its spans sorted from the lowest X
+    pub fn rows(&self) -> impl Iterator<Item=(I, &[Span<I>])> {
+        self.rows.iter().map(|(y, spans)| (*y, spans.as_slice()))
+    }
+
+    pub fn contains(&self, x: I, y: I) -> bool {
+        let Some(spans) = self.rows.get(&y) else { return false };
+        let after = spans.partition_point(|span| span.0 <= x);
+        after > 0 && x <= spans[after - 1].1
+    }
+
+    /// Every tile in the region, row by row from the lowest Y
+    pub fn addresses(&self) -> impl Iterator<Item=(I, I)> + '_ {
+        self.rows().flat_map(|(y, spans)| {
+            spans.iter().flat_map(move |span| span.into_iter().map(move |x| (x, y)))
+        })
+    }
+}
+
+// what a scanline walk does with the spans it finds: `inside` must start failing
+// for the tiles of a span once it's been given to `fill`, or the walk never ends
+trait ScanTarget<I: Step + Copy> {
+    fn inside(&self, x: I, y: I) -> bool;
+    fn fill(&mut self, span: Span<I>, y: I);
+    fn is_done(&self) -> bool { false }
+
+    // the lowest X in `xs` that's inside, for targets that can do better than testing one tile at a time
+    fn first_inside(&self, xs: Span<I>, y: I) -> Option<I> {
+        xs.into_iter().find(|&x| self.inside(x, y))
+    }
+
+    // the widest span through `x` that's all inside, if `x` is; likewise overridable
+    fn span_through(&self, x: I, y: I) -> Option<Span<I>> {
+        expand_range(x, |x| self.inside(x, y))
+    }
+}
+
+// how many tiles `Paint` reads from a row at a time, when growing a span
+const SPAN_WINDOW: usize = 64;
+
+struct Paint<'t, G, P, T, I> {
+    tiles: &'t mut G,
+    tile_test: P,
+    color: T,
+    clip: Option<((I, I), (I, I))>,
+    remaining: Option<usize>,
+    // whether any tiles were left unfilled for lack of budget
+    refused: bool,
+}
+
+impl<'t, G, P, I, T> ScanTarget<I> for Paint<'t, G, P, T, I>
+    where G: Tiles<I, Tile=T>,
+          P: Fn(&T) -> bool,
+          T: Clone,
+          I: Step + PartialOrd + Copy,
+{
+    fn inside(&self, x: I, y: I) -> bool {
+        let clipped = self.clip.is_some_and(|((min_x, min_y), (max_x, max_y))| {
+            x < min_x || x > max_x || y < min_y || y > max_y
+        });
+        !clipped && self.tiles.get_tile(x, y).is_some_and(|t| (self.tile_test)(t))
+    }
+    fn first_inside(&self, xs: Span<I>, y: I) -> Option<I> {
+        let Span(start, end) = self.clip_span(xs, y)?;
+        match self.tiles.row_slice(y, start..=end) {
+            // tiles past the end of the slice are out of bounds, so they can't be inside either
+            Some(row) => row.iter().position(|t| (self.tile_test)(t)).map(|i| Step::forward(start, i)),
+            None => Span(start, end).into_iter().find(|&x| self.inside(x, y)),
+        }
+    }
+    fn span_through(&self, x: I, y: I) -> Option<Span<I>> {
+        self.inside(x, y).then(|| Span(self.run_end(x, y, Dir::Down), self.run_end(x, y, Dir::Up)))
+    }
+    fn fill(&mut self, span: Span<I>, y: I) {
+        let mut span = span;
+        if let Some(remaining) = self.remaining.as_mut() {
+            let len = span.into_iter().count();
+            if len > *remaining {
+                self.refused = true;
+                if *remaining == 0 {
+                    return;
+                }
+                span.1 = Step::forward(span.0, *remaining - 1);
+                *remaining = 0;
+            } else {
+                *remaining -= len;
+            }
+        }
+        self.tiles.fill_span(y, span.0..=span.1, self.color.clone());
+    }
+    fn is_done(&self) -> bool {
+        self.refused
+    }
+}
+
+impl<'t, G, P, I, T> Paint<'t, G, P, T, I>
+    where G: Tiles<I, Tile=T>,
+          P: Fn(&T) -> bool,
+          T: Clone,
+          I: Step + PartialOrd + Copy,
+{
+    // the part of `xs` in row `y` that's within the clip rectangle, if any
+    fn clip_span(&self, xs: Span<I>, y: I) -> Option<Span<I>> {
+        let Span(mut start, mut end) = xs;
+        if let Some(((min_x, min_y), (max_x, max_y))) = self.clip {
+            if y < min_y || y > max_y {
+                return None;
+            }
+            if start < min_x { start = min_x; }
+            if end > max_x { end = max_x; }
+        }
+        (start <= end).then_some(Span(start, end))
+    }
+
+    // the last X of the run of inside tiles from `x` (which is inside), going up or down the row.
+    // Whole windows of the row are read as slices while they can be, then the rest tile by tile
+    fn run_end(&self, x: I, y: I, dir: Dir) -> I {
+        let mut end = x;
+        loop {
+            let Some(next) = dir.step(end) else { return end };
+            let Some(far) = dir.step_by(next, SPAN_WINDOW - 1) else { break };
+            let window = match dir {
+                Dir::Up => Span(next, far),
+                Dir::Down => Span(far, next),
+            };
+            if self.clip_span(window, y) != Some(window) {
+                break;
+            }
+            let Some(row) = self.tiles.row_slice(y, window.0..=window.1).filter(|row| row.len() == SPAN_WINDOW) else {
+                break
+            };
+            let run = match dir {
+                Dir::Up => row.iter().take_while(|t| (self.tile_test)(t)).count(),
+                Dir::Down => row.iter().rev().take_while(|t| (self.tile_test)(t)).count(),
+            };
+            if run < SPAN_WINDOW {
+                return dir.step_by(end, run).unwrap_or(end);
+            }
+            end = far;
+        }
+        while let Some(next) = dir.step(end).filter(|&next| self.inside(next, y)) {
+            end = next;
+        }
+        end
+    }
+}
+
+struct Query<'t, G, P, I> {
+    tiles: &'t G,
+    tile_test: P,
+    region: Region<I>,
+    max_size: Option<usize>,
+}
+
+impl<'t, G, P, I, T> ScanTarget<I> for Query<'t, G, P, I>
+    where G: ReadTiles<I, Tile=T>,
+          P: Fn(&T) -> bool,
+          I: Step + Ord + Copy,
+{
+    fn inside(&self, x: I, y: I) -> bool {
+        self.tiles.get_tile(x, y).is_some_and(|t| (self.tile_test)(t)) && !self.region.contains(x, y)
+    }
+    fn fill(&mut self, span: Span<I>, y: I) {
+        let mut span = span;
+        let mut len = span.into_iter().count();
+        if let Some(max_size) = self.max_size {
+            let remaining = max_size - self.region.size;
+            if len > remaining {
+                self.region.truncated = true;
+                if remaining == 0 {
+                    return;
+                }
+                span.1 = Step::forward(span.0, remaining - 1);
+                len = remaining;
+            }
+        }
+        let spans = self.region.rows.entry(y).or_default();
+        let index = spans.partition_point(|other| other.0 < span.0);
+        spans.insert(index, span);
+        self.region.size += len;
+    }
+    fn is_done(&self) -> bool {
+        self.region.truncated
+    }
+}
+
+fn scan_fill<S, I>(target: &mut S, start: (I, I), connectivity: Connectivity)
+    where S: ScanTarget<I>,
+          I: Step + PartialOrd + Copy + Debug,
 {
     /* This is an implementation of the "combined-scan-and-fill" algorithm described on Wikipedia:
      *  - Get start color from the starting tile
@@ -28,69 +372,74 @@ pub fn flood_fill<G, I, P, T>(
      *    - Find consecutive Spans in the next row up/down that are adjacent to the parent Span
      *    - If any of those Spans overhangs the parent span, recurse back to the parent row where it overhung
      *    - Also recurse (by pushing a Seed to the stack) into the next row in the current direction
+     *
+     * With 8-connectivity, a span's neighbors in the next row reach one tile further on either side,
+     * so each seed holds the range of X to scan (the parent span, widened) rather than the span itself.
      */
 
-    let start_color: T = match tiles.get_tile(start.0, start.1) {
-        Some(t) => t.clone(),
-        None => return,
+    let reach = match connectivity {
+        Connectivity::Four => 0,
+        Connectivity::Eight => 1,
     };
+    let widen = |Span(start, end): Span<I>| Span(
+        Step::backward_checked(start, reach).unwrap_or(start),
+        Step::forward_checked(end, reach).unwrap_or(end),
+    );
 
     let mut seed_stack: Vec<(Span<I>, I, Dir)> = Vec::new();
 
-    if let Some(start_range) = expand_range(start.0, &GridRowFloodTest {
-        grid: tiles,
-        tile_test: |c: &T| color_equivalence(c, &start_color),
-        y: start.1,
-    }) {
+    if let Some(start_range) = target.span_through(start.0, start.1) {
         // fill the initial row
-        for x in start_range.into_iter() {
-            tiles.set_tile(x, start.1, color.clone());
+        target.fill(start_range, start.1);
+        if target.is_done() {
+            return;
         }
 
         // seed the next row up
         if let Some(up_y) = Dir::Up.step(start.1) {
-            seed_stack.push((start_range, up_y, Dir::Up));
+            seed_stack.push((widen(start_range), up_y, Dir::Up));
         }
 
         // seed the next row down
         if let Some(down_y) = Dir::Down.step(start.1) {
-            seed_stack.push((start_range, down_y, Dir::Down));
+            seed_stack.push((widen(start_range), down_y, Dir::Down));
         }
     }
 
     // Stack-based recursion by pushing and popping to the seed_stack.
-    // `parent_range` is a x=min..=max span representing a series of consecutive filled pixels from the previous row.
+    // `scan_range` is a x=min..=max span of the tiles next to (or diagonal from) the consecutive filled tiles of the previous row.
     // `y` is the current row coordinate
     // `dir` is the up/down direction that was taken to get from the parent row to the current `y`
-    while let Some((parent_range, y, dir)) = seed_stack.pop() {
-        let Span(parent_start, parent_end) = parent_range;
+    while let Some((scan_range, y, dir)) = seed_stack.pop() {
+        let Span(scan_start, scan_end) = scan_range;
 
-        // precalculate the "overhang" thresholds for when a parent span reaches past the parent range
-        let parent_start_minus_2 = Step::backward_checked(parent_start, 2);
-        let parent_end_plus_2 = Step::forward_checked(parent_end, 2);
+        // precalculate the "overhang" thresholds for when a child span reaches past the scanned range
+        // (the tiles just outside the scanned range were already checked from the parent row)
+        let parent_start_minus_2 = Step::backward_checked(scan_start, 2 - reach);
+        let parent_end_plus_2 = Step::forward_checked(scan_end, 2 - reach);
 
         // scan over the "child" row for Spans of consecutive "inside" tiles
-        let mut child_scan = ChildScan::start(parent_range);
-        while let Some(child_range) = child_scan.next(&GridRowFloodTest {
-            grid: tiles,
+        let mut child_scan = ChildScan::start(scan_range);
+        while let Some(child_range) = child_scan.next(&RowFloodTest {
+            target: &*target,
             y,
-            tile_test: |c: &T| color_equivalence(c, &start_color),
         }) {
 
             // fill the tiles in the child span
-            for x in child_range.into_iter() {
-                tiles.set_tile(x, y, color.clone());
+            target.fill(child_range, y);
+            if target.is_done() {
+                return;
             }
 
             // add a new seed using the child range as a parent, in the same y direction
             if let Some(next_y) = dir.step(y) {
-                seed_stack.push((child_range, next_y, dir));
+                seed_stack.push((widen(child_range), next_y, dir));
             }
 
             // if the child range overhung the parent range, we've passed some obstacle
             // on the previous row, and need to jump back to that row to continue
             if let Some(prev_y) = (!dir).step(y) {
-                let Span(child_start, child_end) = child_range;
+                let Span(child_start, child_end) = widen(child_range);
 
                 if let Some(ps2) = parent_start_minus_2 {
                     if child_start <= ps2 {
@@ -107,6 +456,7 @@ pub fn flood_fill<G, I, P, T>(
     }
 }
 
+// also used along rows, with `Up` as increasing X
 #[derive(Copy, Clone, Debug)]
 enum Dir {
     Up,
@@ -115,9 +465,13 @@ enum Dir {
 
 impl Dir {
     fn step<I: Step>(&self, i: I) -> Option<I> {
+        self.step_by(i, 1)
+    }
+
+    fn step_by<I: Step>(&self, i: I, n: usize) -> Option<I> {
         match *self {
-            Dir::Up => Step::forward_checked(i, 1),
-            Dir::Down => Step::backward_checked(i, 1),
+            Dir::Up => Step::forward_checked(i, n),
+            Dir::Down => Step::backward_checked(i, n),
         }
     }
 }
@@ -132,8 +486,9 @@ impl Not for Dir {
     }
 }
 
-#[derive(Copy, Clone)]
-struct Span<I: Copy>(I, I);
+/// A run of consecutive tiles in a row, from the first X to the last X (inclusive)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Span<I>(pub I, pub I);
 
 impl<I: Copy + Step> IntoIterator for Span<I> {
     type Item = I;
@@ -145,38 +500,40 @@ impl<I: Copy + Step> IntoIterator for Span<I> {
 }
 
 trait FloodTest<I> {
-    fn inside(&self, x: I) -> bool;
+    fn first_inside(&self, xs: Span<I>) -> Option<I>;
+    fn span_through(&self, x: I) -> Option<Span<I>>;
 }
 
-struct GridRowFloodTest<'g, G, P, I> {
-    grid: &'g G,
-    tile_test: P,
+struct RowFloodTest<'s, S, I> {
+    target: &'s S,
     y: I,
 }
 
-impl<'g, G, P, I, T> FloodTest<I> for GridRowFloodTest<'g, G, P, I>
-    where G: Tiles<I, Tile=T>,
-          P: Fn(&T) -> bool,
-          I: Copy,
+impl<'s, S, I> FloodTest<I> for RowFloodTest<'s, S, I>
+    where S: ScanTarget<I>,
+          I: Step + Copy,
 {
-    fn inside(&self, x: I) -> bool {
-        self.grid.get_tile(x, self.y).is_some_and(|t| (self.tile_test)(t))
+    fn first_inside(&self, xs: Span<I>) -> Option<I> {
+        self.target.first_inside(xs, self.y)
+    }
+    fn span_through(&self, x: I) -> Option<Span<I>> {
+        self.target.span_through(x, self.y)
     }
 }
 
-fn expand_range<F, I>(x: I, test: &F) -> Option<Span<I>>
-    where F: FloodTest<I>,
+fn expand_range<F, I>(x: I, inside: F) -> Option<Span<I>>
+    where F: Fn(I) -> bool,
           I: Copy + Step,
 {
-    if test.inside(x) {
+    if inside(x) {
         let x_min = DescendFrom(Some(x))
-            .take_while(|x| test.inside(*x))
+            .take_while(|x| inside(*x))
             .last()
-            .unwrap_or_else(|| x);
+            .unwrap_or(x);
         let x_max = AscendFrom(Some(x))
-            .take_while(|x| test.inside(*x))
+            .take_while(|x| inside(*x))
             .last()
-            .unwrap_or_else(|| x);
+            .unwrap_or(x);
         Some(Span(x_min, x_max))
     } else {
         None
@@ -217,8 +574,8 @@ fn next_child_range_right<F, I>(parent_max_x: I, current_x: I, test: &F) -> Opti
 {
     // Find the leftmost X in [start_x, parent_max_x] that passes the test.
     // This represents a tile adjacent to an already-filled tile in the parent row.
-    let x_min = (current_x..=parent_max_x).find(|x| test.inside(*x))?;
-    expand_range(x_min, test)
+    let x_min = test.first_inside(Span(current_x, parent_max_x))?;
+    test.span_through(x_min)
 }
 
 struct DescendFrom<I>(Option<I>);
@@ -243,4 +600,159 @@ impl<I: Copy + Step> Iterator for AscendFrom<I> {
         self.0 = next.clone();
         next
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+
+    use crate::grid::{Grid, TileAddress};
+
+    use super::*;
+
+    // the tiles a fill from `start` should reach, found the slow way
+    fn reachable<F>(grid: &Grid<u8>, start: TileAddress, connectivity: Connectivity, within: F) -> HashSet<TileAddress>
+        where F: Fn(&TileAddress) -> bool
+    {
+        let color = grid[start];
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(addr) = queue.pop_front() {
+            let neighbors: Vec<TileAddress> = match connectivity {
+                Connectivity::Four => grid.neighbors4(addr).collect(),
+                Connectivity::Eight => grid.neighbors8(addr).collect(),
+            };
+            for neighbor in neighbors {
+                if grid[neighbor] == color && within(&neighbor) && seen.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        seen
+    }
+
+    fn filled(grid: &Grid<u8>) -> HashSet<TileAddress> {
+        grid.addresses().filter(|&addr| grid[addr] == 9).collect()
+    }
+
+    #[test]
+    fn rows_are_clamped_to_the_grid() {
+        let grid = Grid::from_fn(5, 2, |TileAddress(x, _)| x as u8);
+        assert_eq!(grid.row_slice(1, 3..=8), Some(&[3, 4][..]));
+        assert_eq!(grid.row_slice(1, 5..=8), Some(&[][..]));
+        assert_eq!(grid.row_slice(2, 0..=1), Some(&[][..]));
+    }
+
+    #[test]
+    fn long_rows_fill_the_same_as_tile_by_tile() {
+        // runs much longer than a slice window, broken up by a few walls with gaps in them
+        let grid = Grid::from_fn(300, 40, |TileAddress(x, y)| {
+            let wall = (x % 97 == 50 && y % 13 != 5) || (y == 20 && x % 71 != 3);
+            wall as u8
+        });
+        for start in [TileAddress(0, 0), TileAddress(299, 39), TileAddress(150, 20)] {
+            let mut painted = grid.clone();
+            flood_fill(&mut painted, (start.0, start.1), |a: &u8, b: &u8| a == b, 9);
+            assert_eq!(filled(&painted), reachable(&grid, start, Connectivity::Four, |_| true));
+        }
+
+        // clipped partway through a window
+        let mut painted = grid.clone();
+        let options = FillOptions { clip: Some(((10, 0), (200, 10))), ..FillOptions::default() };
+        flood_fill_with(&mut painted, (100, 5), |a: &u8, b: &u8| a == b, 9, &options);
+        let within = |addr: &TileAddress| (10..=200).contains(&addr.0) && addr.1 <= 10;
+        assert_eq!(filled(&painted), reachable(&grid, TileAddress(100, 5), Connectivity::Four, within));
+    }
+
+    // two 3x3 blocks of 0s that only touch at the corner between (2, 2) and (3, 3)
+    fn pinched() -> Grid<u8> {
+        Grid::from_fn(6, 6, |TileAddress(x, y)| !((x < 3 && y < 3) || (x >= 3 && y >= 3)) as u8)
+    }
+
+    fn fill_pinched(start: (u32, u32), options: &FillOptions<u32>) -> (Grid<u8>, bool) {
+        let mut painted = pinched();
+        let truncated = flood_fill_with(&mut painted, start, |a: &u8, b: &u8| a == b, 9, options);
+        (painted, truncated)
+    }
+
+    #[test]
+    fn only_eight_connectivity_crosses_a_diagonal_pinch() {
+        let grid = pinched();
+        for (connectivity, size) in [(Connectivity::Four, 9), (Connectivity::Eight, 18)] {
+            let (painted, truncated) = fill_pinched((0, 0), &FillOptions { connectivity, ..FillOptions::default() });
+            assert!(!truncated);
+            assert_eq!(filled(&painted).len(), size);
+            assert_eq!(filled(&painted), reachable(&grid, TileAddress(0, 0), connectivity, |_| true));
+        }
+    }
+
+    #[test]
+    fn fills_stay_within_the_clip() {
+        let grid = pinched();
+        let clips = [((1, 1), (4, 4)), ((2, 0), (10, 10)), ((0, 0), (2, 2))];
+        for clip @ ((min_x, min_y), (max_x, max_y)) in clips {
+            for connectivity in [Connectivity::Four, Connectivity::Eight] {
+                let options = FillOptions { connectivity, clip: Some(clip), budget: None };
+                let (painted, _) = fill_pinched((2, 2), &options);
+                let within = |addr: &TileAddress| (min_x..=max_x).contains(&addr.0) && (min_y..=max_y).contains(&addr.1);
+                assert_eq!(filled(&painted), reachable(&grid, TileAddress(2, 2), connectivity, within), "{clip:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn budgets_only_truncate_when_tiles_are_left_unfilled() {
+        for (budget, size, truncated) in [(8, 8, true), (9, 9, false), (10, 9, false), (0, 0, true)] {
+            let options = FillOptions { budget: Some(budget), ..FillOptions::default() };
+            let (painted, was_truncated) = fill_pinched((0, 0), &options);
+            assert_eq!(filled(&painted).len(), size, "budget {budget}");
+            assert_eq!(was_truncated, truncated, "budget {budget}");
+        }
+
+        // the budget runs out partway through a row
+        let options = FillOptions { budget: Some(4), ..FillOptions::default() };
+        let (painted, truncated) = fill_pinched((0, 0), &options);
+        assert!(truncated);
+        assert!(filled(&painted).is_subset(&reachable(&pinched(), TileAddress(0, 0), Connectivity::Four, |_| true)));
+    }
+
+    // an L of `true`: a column at x = 1 from y = 1 to 3, and a row at y = 1 from x = 1 to 4
+    fn l_shape() -> Grid<bool> {
+        Grid::from_fn(6, 5, |TileAddress(x, y)| (x == 1 && (1..=3).contains(&y)) || (y == 1 && (1..=4).contains(&x)))
+    }
+
+    #[test]
+    fn regions_describe_the_tiles_they_cover() {
+        let grid = l_shape();
+        let region = query_region(&grid, (1, 3), |t: &bool| *t, Connectivity::Four, None);
+        assert_eq!(region.size(), 6);
+        assert!(!region.is_empty());
+        assert!(!region.is_truncated());
+        assert_eq!(region.bounds(), Some(((1, 1), (4, 3))));
+        let rows: Vec<(u32, Vec<Span<u32>>)> = region.rows().map(|(y, spans)| (y, spans.to_vec())).collect();
+        assert_eq!(rows, vec![
+            (1, vec![Span(1, 4)]),
+            (2, vec![Span(1, 1)]),
+            (3, vec![Span(1, 1)]),
+        ]);
+        for addr in grid.addresses() {
+            assert_eq!(region.contains(addr.0, addr.1), grid[addr], "{addr:?}");
+        }
+        assert_eq!(region.addresses().count(), 6);
+
+        let outside = query_region(&grid, (0, 0), |t: &bool| *t, Connectivity::Four, None);
+        assert!(outside.is_empty());
+        assert_eq!(outside.bounds(), None);
+    }
+
+    #[test]
+    fn regions_stop_at_their_max_size() {
+        let grid = l_shape();
+        for (max_size, size, truncated) in [(4, 4, true), (6, 6, false), (7, 6, false), (0, 0, true)] {
+            let region = query_region(&grid, (4, 1), |t: &bool| *t, Connectivity::Four, Some(max_size));
+            assert_eq!(region.size(), size, "max_size {max_size}");
+            assert_eq!(region.is_truncated(), truncated, "max_size {max_size}");
+            assert_eq!(region.addresses().count(), size);
+            assert!(region.addresses().all(|(x, y)| grid[TileAddress(x, y)]));
+        }
+    }
+}
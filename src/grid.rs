@@ -1,9 +1,11 @@
 use std::borrow::Borrow;
-use std::ops::{Add, Index, IndexMut};
+use std::collections::VecDeque;
+use std::ops::{Add, Index, IndexMut, RangeInclusive};
 
-use bevy::prelude::{Component, Resource};
+use bevy::prelude::{Component, Resource, Vec2};
 
-use crate::fill::Tiles;
+use crate::cardinal::{Cardinal, Ordinal};
+use crate::fill::{ReadTiles, Tiles};
 use crate::GridDimensions;
 
 #[derive(Component, Copy, Clone, Debug, Hash, Eq, PartialEq)]
@@ -13,6 +15,12 @@ impl TileAddress {
     pub fn as_tuple(&self) -> (u32, u32) {
         (self.0, self.1)
     }
+
+    /// The neighboring address in the given direction (a `Cardinal` or an `Ordinal`),
+    /// or `None` if that would go below zero.
+    pub fn step<D: Into<Ordinal>>(&self, direction: D) -> Option<TileAddress> {
+        *self + direction.into().offset()
+    }
 }
 
 impl Add<(i32, i32)> for TileAddress {
@@ -27,7 +35,95 @@ impl Add<(i32, i32)> for TileAddress {
     }
 }
 
-#[derive(Clone, Resource)]
+/// How to measure the distance between two tiles, for `Grid::within_radius`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Distance {
+    /// The larger of the X and Y distances, so a radius covers a square
+    Chebyshev,
+    /// The X and Y distances added up, so a radius covers a diamond
+    Manhattan,
+    /// Straight-line distance between tile centers, so a radius covers a (pixelated) circle
+    Euclidean,
+}
+
+impl Distance {
+    pub fn between(&self, a: TileAddress, b: TileAddress) -> f32 {
+        let dx = a.0.abs_diff(b.0) as f32;
+        let dy = a.1.abs_diff(b.1) as f32;
+        match self {
+            Distance::Chebyshev => dx.max(dy),
+            Distance::Manhattan => dx + dy,
+            Distance::Euclidean => dx.hypot(dy),
+        }
+    }
+}
+
+/// A rectangle of tiles, `width` by `height` with its bottom-left tile at `min`
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct TileRect {
+    pub min: TileAddress,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TileRect {
+    pub fn new(min: TileAddress, width: u32, height: u32) -> Self {
+        TileRect { min, width, height }
+    }
+
+    /// The rectangle with `a` and `b` as opposite corners (inclusive), in either order
+    pub fn from_corners(a: TileAddress, b: TileAddress) -> Self {
+        let min = TileAddress(a.0.min(b.0), a.1.min(b.1));
+        TileRect {
+            min,
+            width: a.0.abs_diff(b.0) + 1,
+            height: a.1.abs_diff(b.1) + 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The top-right tile, if the rectangle isn't empty
+    pub fn max(&self) -> Option<TileAddress> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(TileAddress(self.min.0 + self.width - 1, self.min.1 + self.height - 1))
+        }
+    }
+
+    pub fn contains(&self, pos: &TileAddress) -> bool {
+        pos.0 >= self.min.0 && pos.1 >= self.min.1
+            && pos.0 - self.min.0 < self.width && pos.1 - self.min.1 < self.height
+    }
+
+    /// The part of this rectangle that's also in `other`, which may be empty
+    pub fn intersect(&self, other: &TileRect) -> TileRect {
+        let min = TileAddress(self.min.0.max(other.min.0), self.min.1.max(other.min.1));
+        let end_x = (self.min.0 + self.width).min(other.min.0 + other.width);
+        let end_y = (self.min.1 + self.height).min(other.min.1 + other.height);
+        TileRect {
+            min,
+            width: end_x.saturating_sub(min.0),
+            height: end_y.saturating_sub(min.1),
+        }
+    }
+
+    /// Row by row from the bottom
+    pub fn addresses(&self) -> impl Iterator<Item=TileAddress> {
+        let TileRect { min, width, height } = *self;
+        (min.1..min.1 + height).flat_map(move |y| (min.0..min.0 + width).map(move |x| TileAddress(x, y)))
+    }
+}
+
+/// Anything with tiles at every address within a rectangle, like a `Grid`
+pub trait Bounded {
+    fn bounds(&self) -> TileRect;
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Resource)]
 pub struct Grid<T> {
     width: u32,
     height: u32,
@@ -51,6 +147,19 @@ impl<T: Default + Clone> Grid<T> {
 }
 
 impl<T> Grid<T> {
+    /// A grid where each tile is `f` of its address
+    pub fn from_fn<F: FnMut(TileAddress) -> T>(width: u32, height: u32, f: F) -> Self {
+        let tiles = (0..height)
+            .flat_map(|y| (0..width).map(move |x| TileAddress(x, y)))
+            .map(f)
+            .collect();
+        Grid {
+            width,
+            height,
+            tiles,
+        }
+    }
+
     pub fn width(&self) -> u32 { self.width }
 
     pub fn height(&self) -> u32 { self.height }
@@ -72,6 +181,201 @@ impl<T> Grid<T> {
             })
         })
     }
+
+    pub fn contains(&self, pos: &TileAddress) -> bool {
+        pos.0 < self.width && pos.1 < self.height
+    }
+
+    /// Every address paired with its tile, row by row from the bottom
+    pub fn entries(&self) -> impl Iterator<Item=(TileAddress, &T)> {
+        self.addresses().zip(self.tiles.iter())
+    }
+
+    /// Pairs each of the `addresses` that's in bounds with its tile, e.g. to turn
+    /// one of the address queries below into an iterator of tiles
+    pub fn entries_at<'a, I>(&'a self, addresses: I) -> impl Iterator<Item=(TileAddress, &'a T)> + 'a
+        where I: IntoIterator<Item=TileAddress> + 'a
+    {
+        addresses.into_iter().filter_map(move |addr| self.tile_at(&addr).map(|tile| (addr, tile)))
+    }
+
+    /// The tiles of row `y`, from west to east
+    pub fn row(&self, y: u32) -> impl Iterator<Item=(TileAddress, &T)> {
+        let width = if y < self.height { self.width } else { 0 };
+        (0..width).map(move |x| (TileAddress(x, y), &self[TileAddress(x, y)]))
+    }
+
+    /// The tiles of column `x`, from south to north
+    pub fn column(&self, x: u32) -> impl Iterator<Item=(TileAddress, &T)> {
+        let height = if x < self.width { self.height } else { 0 };
+        (0..height).map(move |y| (TileAddress(x, y), &self[TileAddress(x, y)]))
+    }
+
+    /// The in-bounds orthogonal neighbors of `pos`, clockwise from North
+    pub fn neighbors4(&self, pos: TileAddress) -> impl Iterator<Item=TileAddress> + '_ {
+        Cardinal::iter().filter_map(move |cardinal| pos.step(cardinal).filter(|n| self.contains(n)))
+    }
+
+    /// The in-bounds orthogonal and diagonal neighbors of `pos`, clockwise from North
+    pub fn neighbors8(&self, pos: TileAddress) -> impl Iterator<Item=TileAddress> + '_ {
+        Ordinal::iter().filter_map(move |ordinal| pos.step(ordinal).filter(|n| self.contains(n)))
+    }
+
+    /// The in-bounds tiles within `radius` of `center` (inclusive), as measured by `distance`
+    pub fn within_radius(&self, center: TileAddress, radius: f32, distance: Distance) -> impl Iterator<Item=TileAddress> {
+        let reach = radius.max(0.).floor() as u32;
+        let min_x = center.0.saturating_sub(reach);
+        let min_y = center.1.saturating_sub(reach);
+        let max_x = center.0.saturating_add(reach).min(self.width.saturating_sub(1));
+        let max_y = center.1.saturating_add(reach).min(self.height.saturating_sub(1));
+        let empty = self.width == 0 || self.height == 0 || min_x > max_x || min_y > max_y;
+        let (max_x, max_y) = if empty { (0, 0) } else { (max_x + 1, max_y + 1) };
+
+        (min_y..max_y)
+            .flat_map(move |y| (min_x..max_x).map(move |x| TileAddress(x, y)))
+            .filter(move |addr| distance.between(center, *addr) <= radius)
+    }
+
+    /// The rectangle covering the whole grid
+    pub fn bounds(&self) -> TileRect {
+        TileRect::new(TileAddress(0, 0), self.width, self.height)
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            tiles: self.tiles.iter().map(f).collect(),
+        }
+    }
+
+    /// Combines this grid with another of the same size, tile by tile
+    pub fn zip_with<U, V, F>(&self, other: &Grid<U>, mut f: F) -> Grid<V>
+        where F: FnMut(&T, &U) -> V
+    {
+        assert!(self.width == other.width && self.height == other.height, "zipped grids must be the same size");
+        Grid {
+            width: self.width,
+            height: self.height,
+            tiles: self.tiles.iter().zip(other.tiles.iter()).map(|(a, b)| f(a, b)).collect(),
+        }
+    }
+
+    /// Combines `other` into this grid, with `other`'s bottom-left tile landing at `offset`
+    /// (which may be negative). Tiles of `other` that land out of bounds are dropped.
+    pub fn paste<U, F>(&mut self, other: &Grid<U>, offset: (i32, i32), blend: F)
+        where F: Fn(&T, &U) -> T
+    {
+        for (addr, tile) in other.entries() {
+            if let Some(target) = (addr + offset).and_then(|target| self.tile_at_mut(&target)) {
+                *target = blend(target, tile);
+            }
+        }
+    }
+
+    /// A read-only window onto part of the grid, clipped to the grid's bounds. It implements
+    /// `ReadTiles` and `Bounded`, so e.g. `collect_borders` can look at just that part.
+    pub fn view(&self, rect: TileRect) -> GridView<'_, T> {
+        GridView {
+            rect: rect.intersect(&self.bounds()),
+            grid: self,
+        }
+    }
+
+    /// A window onto part of the grid, clipped to the grid's bounds. It implements `Tiles`,
+    /// so e.g. `flood_fill` can be confined to it.
+    pub fn view_mut(&mut self, rect: TileRect) -> GridViewMut<'_, T> {
+        GridViewMut {
+            rect: rect.intersect(&self.bounds()),
+            grid: self,
+        }
+    }
+
+    /// Breadth-first search outward from `from` (through 4-connected steps, regardless of
+    /// tile contents) for the closest tile that satisfies the `predicate`.
+    pub fn nearest_where<P: Fn(&T) -> bool>(&self, from: TileAddress, predicate: P) -> Option<TileAddress> {
+        self.tile_at(&from)?;
+        let mut visited = vec![false; self.tiles.len()];
+        let mut queue = VecDeque::new();
+        visited[(from.1 * self.width + from.0) as usize] = true;
+        queue.push_back(from);
+
+        while let Some(addr) = queue.pop_front() {
+            if predicate(&self[addr]) {
+                return Some(addr);
+            }
+            for next in self.neighbors4(addr) {
+                let idx = (next.1 * self.width + next.0) as usize;
+                if !visited[idx] {
+                    visited[idx] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn fill_rect(&mut self, rect: TileRect, tile: T) {
+        for addr in rect.intersect(&self.bounds()).addresses() {
+            self[addr] = tile.clone();
+        }
+    }
+
+    /// A copy of the part of the grid inside `rect`, clipped to the grid's bounds
+    pub fn crop(&self, rect: TileRect) -> Grid<T> {
+        let rect = rect.intersect(&self.bounds());
+        Grid::from_fn(rect.width, rect.height, |TileAddress(x, y)| {
+            self[TileAddress(rect.min.0 + x, rect.min.1 + y)].clone()
+        })
+    }
+
+    /// Rotates the grid a quarter turn clockwise
+    pub fn rotate90(&self) -> Grid<T> {
+        Grid::from_fn(self.height, self.width, |TileAddress(x, y)| {
+            self[TileAddress(self.width - 1 - y, x)].clone()
+        })
+    }
+
+    /// Rotates the grid a quarter turn counter-clockwise
+    pub fn rotate90_ccw(&self) -> Grid<T> {
+        Grid::from_fn(self.height, self.width, |TileAddress(x, y)| {
+            self[TileAddress(y, self.height - 1 - x)].clone()
+        })
+    }
+
+    /// Mirrors the grid west to east
+    pub fn flip_x(&self) -> Grid<T> {
+        Grid::from_fn(self.width, self.height, |TileAddress(x, y)| {
+            self[TileAddress(self.width - 1 - x, y)].clone()
+        })
+    }
+
+    /// Mirrors the grid south to north
+    pub fn flip_y(&self) -> Grid<T> {
+        Grid::from_fn(self.width, self.height, |TileAddress(x, y)| {
+            self[TileAddress(x, self.height - 1 - y)].clone()
+        })
+    }
+
+    /// Swaps the X and Y axes, mirroring the grid across its bottom-left to top-right diagonal
+    pub fn transpose(&self) -> Grid<T> {
+        Grid::from_fn(self.height, self.width, |TileAddress(x, y)| {
+            self[TileAddress(y, x)].clone()
+        })
+    }
+
+    /// Resizes the grid, filling new space with `fill`. The `anchor` is the point of the grid that
+    /// stays put, as a fraction of its size: (0, 0) keeps the bottom-left tiles where they are,
+    /// (0.5, 0.5) grows or shrinks the grid evenly around its center, and (1, 1) keeps the top-right tiles.
+    pub fn resize(&self, width: u32, height: u32, anchor: Vec2, fill: T) -> Grid<T> {
+        let offset = |old: u32, new: u32, anchor: f32| ((new as f32 - old as f32) * anchor.clamp(0., 1.)).round() as i32;
+        let offset = (offset(self.width, width, anchor.x), offset(self.height, height, anchor.y));
+        let mut resized = Grid::from_fn(width, height, |_| fill.clone());
+        resized.paste(self, offset, |_, tile| tile.clone());
+        resized
+    }
 }
 
 impl<T, A: Borrow<TileAddress>> Index<A> for Grid<T> {
@@ -88,16 +392,281 @@ impl<T, A: Borrow<TileAddress>> IndexMut<A> for Grid<T> {
     }
 }
 
+impl<T> Bounded for Grid<T> {
+    fn bounds(&self) -> TileRect {
+        Grid::bounds(self)
+    }
+}
+
+impl<T> ReadTiles<u32> for Grid<T>
+    where T: Sized + PartialEq
+{
+    type Tile = T;
+    fn get_tile(&self, x: u32, y: u32) -> Option<&T> {
+        self.tile_at(&TileAddress(x, y))
+    }
+    fn row_slice(&self, y: u32, xs: RangeInclusive<u32>) -> Option<&[T]> {
+        let (start, end) = (*xs.start(), (*xs.end()).min(self.width.saturating_sub(1)));
+        if y < self.height && start <= end {
+            let row = (y * self.width) as usize;
+            Some(&self.tiles[row + start as usize..=row + end as usize])
+        } else {
+            Some(&[])
+        }
+    }
+}
+
 impl<T> Tiles<u32> for Grid<T>
     where T: Sized + PartialEq
+{
+    fn set_tile(&mut self, x: u32, y: u32, tile: T) {
+        if let Some(state) = self.tile_at_mut(&TileAddress(x, y)) {
+            *state = tile;
+        }
+    }
+    fn fill_span(&mut self, y: u32, xs: RangeInclusive<u32>, tile: T)
+        where T: Clone
+    {
+        let (start, end) = (*xs.start(), (*xs.end()).min(self.width.saturating_sub(1)));
+        if y < self.height && start <= end {
+            let row = (y * self.width) as usize;
+            self.tiles[row + start as usize..=row + end as usize].fill(tile);
+        }
+    }
+}
+
+/// A read-only window onto part of a `Grid`, with its own coordinates starting from (0, 0)
+/// at the bottom-left of the window.
+pub struct GridView<'a, T> {
+    grid: &'a Grid<T>,
+    rect: TileRect,
+}
+
+impl<'a, T> GridView<'a, T> {
+    pub fn width(&self) -> u32 { self.rect.width }
+
+    pub fn height(&self) -> u32 { self.rect.height }
+
+    /// Where the window sits in the underlying grid
+    pub fn rect(&self) -> TileRect {
+        self.rect
+    }
+
+    /// Converts an address within the window to one in the underlying grid
+    pub fn to_grid_address(&self, pos: &TileAddress) -> Option<TileAddress> {
+        if pos.0 < self.rect.width && pos.1 < self.rect.height {
+            Some(TileAddress(self.rect.min.0 + pos.0, self.rect.min.1 + pos.1))
+        } else {
+            None
+        }
+    }
+
+    pub fn tile_at(&self, pos: &TileAddress) -> Option<&'a T> {
+        let grid = self.grid;
+        grid.tile_at(&self.to_grid_address(pos)?)
+    }
+
+    /// Addresses within the window, row by row from the bottom
+    pub fn addresses(&self) -> impl Iterator<Item=TileAddress> {
+        TileRect::new(TileAddress(0, 0), self.rect.width, self.rect.height).addresses()
+    }
+}
+
+impl<'a, T> Bounded for GridView<'a, T> {
+    fn bounds(&self) -> TileRect {
+        TileRect::new(TileAddress(0, 0), self.rect.width, self.rect.height)
+    }
+}
+
+impl<'a, T> ReadTiles<u32> for GridView<'a, T>
+    where T: Sized + PartialEq
 {
     type Tile = T;
     fn get_tile(&self, x: u32, y: u32) -> Option<&T> {
         self.tile_at(&TileAddress(x, y))
     }
+    fn row_slice(&self, y: u32, xs: RangeInclusive<u32>) -> Option<&[T]> {
+        let TileRect { min, width, height } = self.rect;
+        let end = (*xs.end()).min(width.saturating_sub(1));
+        if y < height && *xs.start() <= end {
+            self.grid.row_slice(min.1 + y, min.0 + xs.start()..=min.0 + end)
+        } else {
+            Some(&[])
+        }
+    }
+}
+
+/// A window onto part of a `Grid`, with its own coordinates starting from (0, 0)
+/// at the bottom-left of the window.
+pub struct GridViewMut<'a, T> {
+    grid: &'a mut Grid<T>,
+    rect: TileRect,
+}
+
+impl<'a, T> GridViewMut<'a, T> {
+    pub fn width(&self) -> u32 { self.rect.width }
+
+    pub fn height(&self) -> u32 { self.rect.height }
+
+    /// Where the window sits in the underlying grid
+    pub fn rect(&self) -> TileRect {
+        self.rect
+    }
+
+    pub fn as_view(&self) -> GridView<'_, T> {
+        GridView {
+            grid: self.grid,
+            rect: self.rect,
+        }
+    }
+
+    pub fn tile_at(&self, pos: &TileAddress) -> Option<&T> {
+        let addr = self.as_view().to_grid_address(pos)?;
+        self.grid.tile_at(&addr)
+    }
+
+    pub fn tile_at_mut(&mut self, pos: &TileAddress) -> Option<&mut T> {
+        let addr = self.as_view().to_grid_address(pos)?;
+        self.grid.tile_at_mut(&addr)
+    }
+}
+
+impl<'a, T> Bounded for GridViewMut<'a, T> {
+    fn bounds(&self) -> TileRect {
+        self.as_view().bounds()
+    }
+}
+
+impl<'a, T> ReadTiles<u32> for GridViewMut<'a, T>
+    where T: Sized + PartialEq
+{
+    type Tile = T;
+    fn get_tile(&self, x: u32, y: u32) -> Option<&T> {
+        self.tile_at(&TileAddress(x, y))
+    }
+    fn row_slice(&self, y: u32, xs: RangeInclusive<u32>) -> Option<&[T]> {
+        let TileRect { min, width, height } = self.rect;
+        let end = (*xs.end()).min(width.saturating_sub(1));
+        if y < height && *xs.start() <= end {
+            self.grid.row_slice(min.1 + y, min.0 + xs.start()..=min.0 + end)
+        } else {
+            Some(&[])
+        }
+    }
+}
+
+impl<'a, T> Tiles<u32> for GridViewMut<'a, T>
+    where T: Sized + PartialEq
+{
     fn set_tile(&mut self, x: u32, y: u32, tile: T) {
         if let Some(state) = self.tile_at_mut(&TileAddress(x, y)) {
             *state = tile;
         }
     }
+    fn fill_span(&mut self, y: u32, xs: RangeInclusive<u32>, tile: T)
+        where T: Clone
+    {
+        let TileRect { min, width, height } = self.rect;
+        let end = (*xs.end()).min(width.saturating_sub(1));
+        if y < height && *xs.start() <= end {
+            self.grid.fill_span(min.1 + y, min.0 + xs.start()..=min.0 + end, tile);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::border::{Border, collect_borders};
+    use crate::distance::distance_transform;
+    use crate::fill::{Connectivity, query_region};
+
+    use super::*;
+
+    // a grid from its rows, listed top to bottom so they read the way the grid looks
+    fn from_rows(rows: &[&[u8]]) -> Grid<u8> {
+        let height = rows.len() as u32;
+        Grid::from_fn(rows[0].len() as u32, height, |TileAddress(x, y)| rows[(height - 1 - y) as usize][x as usize])
+    }
+
+    // 10 11 12
+    //  0  1  2
+    fn sample() -> Grid<u8> {
+        Grid::from_fn(3, 2, |TileAddress(x, y)| (y * 10 + x) as u8)
+    }
+
+    #[test]
+    fn rotations() {
+        let grid = sample();
+        assert_eq!(grid.rotate90(), from_rows(&[&[0, 10], &[1, 11], &[2, 12]]));
+        assert_eq!(grid.rotate90_ccw(), from_rows(&[&[12, 2], &[11, 1], &[10, 0]]));
+        assert_eq!(grid.rotate90().rotate90_ccw(), grid);
+        assert_eq!(grid.rotate90().rotate90().rotate90().rotate90(), grid);
+    }
+
+    #[test]
+    fn flips_and_transpose() {
+        let grid = sample();
+        assert_eq!(grid.flip_x(), from_rows(&[&[12, 11, 10], &[2, 1, 0]]));
+        assert_eq!(grid.flip_y(), from_rows(&[&[0, 1, 2], &[10, 11, 12]]));
+        assert_eq!(grid.transpose(), from_rows(&[&[2, 12], &[1, 11], &[0, 10]]));
+        assert_eq!(grid.transpose().transpose(), grid);
+    }
+
+    #[test]
+    fn crop_and_paste() {
+        let grid = sample();
+        assert_eq!(grid.crop(TileRect::new(TileAddress(1, 0), 5, 1)), from_rows(&[&[1, 2]]));
+        assert!(grid.crop(TileRect::new(TileAddress(3, 0), 1, 1)).bounds().is_empty());
+
+        // only the stamp's top-left tile lands on the grid
+        let mut pasted = grid.clone();
+        pasted.paste(&from_rows(&[&[5, 6], &[7, 8]]), (2, -1), |a, b| a + b);
+        assert_eq!(pasted, from_rows(&[&[10, 11, 12], &[0, 1, 7]]));
+    }
+
+    #[test]
+    fn resize_keeps_the_anchor_in_place() {
+        let grid = sample();
+        assert_eq!(grid.resize(4, 3, Vec2::ZERO, 9), from_rows(&[&[9, 9, 9, 9], &[10, 11, 12, 9], &[0, 1, 2, 9]]));
+        assert_eq!(grid.resize(4, 3, Vec2::ONE, 9), from_rows(&[&[9, 10, 11, 12], &[9, 0, 1, 2], &[9, 9, 9, 9]]));
+        assert_eq!(grid.resize(5, 4, Vec2::splat(0.5), 9), from_rows(&[
+            &[9, 9, 9, 9, 9],
+            &[9, 10, 11, 12, 9],
+            &[9, 0, 1, 2, 9],
+            &[9, 9, 9, 9, 9],
+        ]));
+        assert_eq!(grid.resize(1, 1, Vec2::ONE, 9), from_rows(&[&[12]]));
+    }
+
+    fn borders_of<G: ReadTiles<u32, Tile=bool> + Bounded>(grid: &G) -> HashSet<Border> {
+        let mut borders = HashSet::new();
+        collect_borders(grid, &|tile: &bool| *tile, &mut |border| { borders.insert(border); });
+        borders
+    }
+
+    fn region_of<G: ReadTiles<u32, Tile=bool>>(grid: &G) -> Vec<(u32, u32)> {
+        query_region(grid, (0, 0), |tile| *tile, Connectivity::Eight, None).addresses().collect()
+    }
+
+    #[test]
+    fn views_read_like_the_cropped_grid() {
+        let grid = Grid::from_fn(6, 4, |TileAddress(x, y)| (x + y) % 3 != 0);
+        let rect = TileRect::new(TileAddress(2, 1), 3, 2);
+        let cropped = grid.crop(rect);
+        let mut copy = grid.clone();
+        let view_mut = copy.view_mut(rect);
+        let view = grid.view(rect);
+
+        assert_eq!(view.bounds(), cropped.bounds());
+        assert_eq!(borders_of(&view), borders_of(&cropped));
+        assert_eq!(region_of(&view), region_of(&cropped));
+        assert_eq!(distance_transform(&view, |tile| *tile), distance_transform(&cropped, |tile| *tile));
+
+        assert_eq!(view_mut.bounds(), cropped.bounds());
+        assert_eq!(borders_of(&view_mut), borders_of(&cropped));
+        assert_eq!(region_of(&view_mut), region_of(&cropped));
+        assert_eq!(distance_transform(&view_mut, |tile| *tile), distance_transform(&cropped, |tile| *tile));
+    }
 }
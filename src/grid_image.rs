@@ -0,0 +1,134 @@
+use std::path::Path;
+
+use bevy::prelude::Color;
+use image::{ImageResult, Rgba, RgbaImage};
+
+use crate::grid::{Grid, TileAddress};
+
+/// A fixed set of tile values, each paired with the color it is drawn as.
+/// Used to turn image pixels back into tiles by picking the closest color.
+pub struct Palette<T> {
+    entries: Vec<(T, [u8; 4])>,
+}
+
+impl<T> Palette<T> {
+    pub fn new<I: IntoIterator<Item=T>, F: Fn(&T) -> Color>(tiles: I, as_color: F) -> Self {
+        let entries = tiles.into_iter()
+            .map(|tile| {
+                let rgba = as_color(&tile).as_rgba_u8();
+                (tile, rgba)
+            })
+            .collect();
+        Palette { entries }
+    }
+
+    /// Finds the palette entry whose color is closest (by squared RGBA distance) to `rgba`.
+    /// Returns `None` only if the palette is empty.
+    pub fn nearest(&self, rgba: [u8; 4]) -> Option<&T> {
+        self.entries
+            .iter()
+            .min_by_key(|(_, color)| color_distance_sq(*color, rgba))
+            .map(|(tile, _)| tile)
+    }
+}
+
+fn color_distance_sq(a: [u8; 4], b: [u8; 4]) -> u32 {
+    a.iter().zip(b.iter())
+        .map(|(&ca, &cb)| {
+            let d = ca.abs_diff(cb) as u32;
+            d * d
+        })
+        .sum()
+}
+
+/// Renders the grid to an image on the CPU, as a `scale × scale` block of pixels per tile.
+///
+/// Grid rows are counted upward from Y=0, while image rows are counted downward,
+/// so the bottom row of the grid ends up as the bottom row of the image.
+pub fn grid_to_image<T, F>(grid: &Grid<T>, as_color: F, scale: u32) -> RgbaImage
+    where F: Fn(&T) -> Color
+{
+    let scale = scale.max(1);
+    let height = grid.height();
+    RgbaImage::from_fn(grid.width() * scale, height * scale, |px, py| {
+        let addr = TileAddress(px / scale, height - 1 - py / scale);
+        Rgba(as_color(&grid[addr]).as_rgba_u8())
+    })
+}
+
+/// Reads tiles back out of an image laid out the way `grid_to_image` writes them.
+/// Each tile is taken from the center pixel of its `scale × scale` block,
+/// and matched to the nearest color in the `palette`.
+pub fn image_to_grid<T>(image: &RgbaImage, palette: &Palette<T>, scale: u32) -> Grid<T>
+    where T: Default + Clone
+{
+    let scale = scale.max(1);
+    let width = image.width() / scale;
+    let height = image.height() / scale;
+    let mut grid = Grid::new(width, height);
+    for addr in grid.addresses() {
+        let px = addr.0 * scale + scale / 2;
+        let py = (height - 1 - addr.1) * scale + scale / 2;
+        if let Some(tile) = palette.nearest(image.get_pixel(px, py).0) {
+            grid[addr] = tile.clone();
+        }
+    }
+    grid
+}
+
+pub fn save_png<T, F, P>(grid: &Grid<T>, as_color: F, scale: u32, path: P) -> ImageResult<()>
+    where F: Fn(&T) -> Color,
+          P: AsRef<Path>,
+{
+    grid_to_image(grid, as_color, scale).save_with_format(path, image::ImageFormat::Png)
+}
+
+pub fn load_png<T, P>(path: P, palette: &Palette<T>, scale: u32) -> ImageResult<Grid<T>>
+    where T: Default + Clone,
+          P: AsRef<Path>,
+{
+    let image = image::open(path)?.into_rgba8();
+    Ok(image_to_grid(&image, palette, scale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TILES: [u8; 3] = [0, 1, 2];
+
+    fn as_color(tile: &u8) -> Color {
+        match tile {
+            0 => Color::BLACK,
+            1 => Color::WHITE,
+            _ => Color::rgb(1.0, 0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn images_round_trip_at_any_scale() {
+        let grid = Grid::from_fn(5, 3, |TileAddress(x, y)| ((x + 2 * y) % 3) as u8);
+        let palette = Palette::new(TILES, as_color);
+        for scale in [1, 3, 8] {
+            let image = grid_to_image(&grid, as_color, scale);
+            assert_eq!(image.dimensions(), (5 * scale, 3 * scale));
+            assert_eq!(image_to_grid(&image, &palette, scale), grid, "scale {scale}");
+        }
+
+        // the bottom row of the grid is the bottom row of the image
+        let image = grid_to_image(&grid, as_color, 8);
+        assert_eq!(image.get_pixel(0, 3 * 8 - 1).0, as_color(&grid[TileAddress(0, 0)]).as_rgba_u8());
+    }
+
+    #[test]
+    fn off_palette_colors_match_the_nearest_entry() {
+        let palette = Palette::new(TILES, as_color);
+        assert_eq!(palette.nearest([255, 0, 0, 255]), Some(&2));
+        assert_eq!(palette.nearest([200, 30, 30, 255]), Some(&2));
+        assert_eq!(palette.nearest([100, 100, 100, 255]), Some(&0));
+        assert_eq!(palette.nearest([180, 170, 190, 255]), Some(&1));
+
+        let empty = Palette::new([], as_color);
+        assert_eq!(empty.nearest([0, 0, 0, 255]), None);
+    }
+}
@@ -0,0 +1,268 @@
+use bevy::prelude::Resource;
+
+use crate::fill::{ReadTiles, Tiles};
+use crate::grid::{Grid, TileAddress};
+
+/// A single tile that was changed by an edit.
+#[derive(Copy, Clone, Debug)]
+pub struct TileChange<T> {
+    pub address: TileAddress,
+    pub before: T,
+    pub after: T,
+}
+
+/// A reversible edit to a grid, recorded as the list of tiles it changed.
+#[derive(Clone, Debug)]
+pub struct TileEdit<T> {
+    changes: Vec<TileChange<T>>,
+}
+
+impl<T> Default for TileEdit<T> {
+    fn default() -> Self {
+        TileEdit { changes: Vec::new() }
+    }
+}
+
+impl<T: Clone + PartialEq> TileEdit<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn changes(&self) -> &[TileChange<T>] {
+        &self.changes
+    }
+
+    /// Sets a tile in the grid, recording the change if it actually changed anything.
+    pub fn set(&mut self, grid: &mut Grid<T>, address: TileAddress, tile: T) {
+        if let Some(current) = grid.tile_at_mut(&address) {
+            if *current != tile {
+                let before = std::mem::replace(current, tile.clone());
+                self.changes.push(TileChange { address, before, after: tile });
+            }
+        }
+    }
+
+    /// Appends another edit's changes to this one, so they can be undone as a unit.
+    pub fn append(&mut self, mut other: TileEdit<T>) {
+        self.changes.append(&mut other.changes);
+    }
+
+    pub fn undo(&self, grid: &mut Grid<T>) {
+        // later changes may overwrite earlier ones, so unwind them in reverse
+        for change in self.changes.iter().rev() {
+            if let Some(tile) = grid.tile_at_mut(&change.address) {
+                *tile = change.before.clone();
+            }
+        }
+    }
+
+    pub fn redo(&self, grid: &mut Grid<T>) {
+        for change in &self.changes {
+            if let Some(tile) = grid.tile_at_mut(&change.address) {
+                *tile = change.after.clone();
+            }
+        }
+    }
+}
+
+/// `Tiles` wrapper that records every `set_tile` into a `TileEdit`,
+/// so that `flood_fill` can be undone.
+pub struct EditRecorder<'a, T> {
+    pub grid: &'a mut Grid<T>,
+    pub edit: &'a mut TileEdit<T>,
+}
+
+impl<'a, T: Clone + PartialEq> ReadTiles<u32> for EditRecorder<'a, T> {
+    type Tile = T;
+
+    fn get_tile(&self, x: u32, y: u32) -> Option<&Self::Tile> {
+        self.grid.tile_at(&TileAddress(x, y))
+    }
+}
+
+impl<'a, T: Clone + PartialEq> Tiles<u32> for EditRecorder<'a, T> {
+    fn set_tile(&mut self, x: u32, y: u32, tile: Self::Tile) {
+        self.edit.set(self.grid, TileAddress(x, y), tile);
+    }
+}
+
+/// Undo and redo stacks of `TileEdit`s.
+///
+/// Edits recorded between `begin_group` and `end_group` are merged into one,
+/// e.g. so a whole brush stroke can be undone at once.
+#[derive(Resource)]
+pub struct EditHistory<T> {
+    undo: Vec<TileEdit<T>>,
+    redo: Vec<TileEdit<T>>,
+    group: Option<TileEdit<T>>,
+    limit: usize,
+}
+
+impl<T> Default for EditHistory<T> {
+    fn default() -> Self {
+        EditHistory::with_limit(256)
+    }
+}
+
+impl<T> EditHistory<T> {
+    /// Creates a history that remembers at most `limit` edits.
+    pub fn with_limit(limit: usize) -> Self {
+        EditHistory {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            group: None,
+            limit,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || self.group.as_ref().is_some_and(|group| !group.changes.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.group = None;
+    }
+}
+
+impl<T: Clone + PartialEq> EditHistory<T> {
+    pub fn begin_group(&mut self) {
+        if self.group.is_none() {
+            self.group = Some(TileEdit::new());
+        }
+    }
+
+    pub fn end_group(&mut self) {
+        if let Some(edit) = self.group.take() {
+            self.push(edit);
+        }
+    }
+
+    /// Pushes an edit that has already been applied. Any redo-able edits are forgotten.
+    pub fn record(&mut self, edit: TileEdit<T>) {
+        match self.group.as_mut() {
+            Some(group) => group.append(edit),
+            None => self.push(edit),
+        }
+    }
+
+    fn push(&mut self, edit: TileEdit<T>) {
+        if edit.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push(edit);
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Reverts the most recent edit, returning it, or `None` if there was nothing to undo.
+    pub fn undo(&mut self, grid: &mut Grid<T>) -> Option<&TileEdit<T>> {
+        self.end_group();
+        let edit = self.undo.pop()?;
+        edit.undo(grid);
+        self.redo.push(edit);
+        self.redo.last()
+    }
+
+    /// Re-applies the most recently undone edit, returning it, or `None` if there was nothing to redo.
+    pub fn redo(&mut self, grid: &mut Grid<T>) -> Option<&TileEdit<T>> {
+        self.end_group();
+        let edit = self.redo.pop()?;
+        edit.redo(grid);
+        self.undo.push(edit);
+        self.undo.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paint(grid: &mut Grid<u8>, history: &mut EditHistory<u8>, x: u32, tile: u8) {
+        let mut edit = TileEdit::new();
+        edit.set(grid, TileAddress(x, 0), tile);
+        history.record(edit);
+    }
+
+    fn row(grid: &Grid<u8>) -> Vec<u8> {
+        (0..grid.width()).map(|x| grid[TileAddress(x, 0)]).collect()
+    }
+
+    #[test]
+    fn undo_and_redo_on_an_empty_history_do_nothing() {
+        let mut grid = Grid::new(3, 1);
+        let mut history = EditHistory::default();
+        assert!(!history.can_undo() && !history.can_redo());
+        assert!(history.undo(&mut grid).is_none());
+        assert!(history.redo(&mut grid).is_none());
+        assert_eq!(row(&grid), [0, 0, 0]);
+
+        // edits that don't change anything aren't recorded
+        paint(&mut grid, &mut history, 1, 0);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn a_new_edit_after_an_undo_clears_the_redo_stack() {
+        let mut grid = Grid::new(3, 1);
+        let mut history = EditHistory::default();
+        paint(&mut grid, &mut history, 0, 1);
+        paint(&mut grid, &mut history, 1, 2);
+
+        assert!(history.undo(&mut grid).is_some());
+        assert_eq!(row(&grid), [1, 0, 0]);
+        assert!(history.can_redo());
+        assert!(history.redo(&mut grid).is_some());
+        assert_eq!(row(&grid), [1, 2, 0]);
+
+        history.undo(&mut grid);
+        paint(&mut grid, &mut history, 2, 3);
+        assert!(!history.can_redo());
+        assert!(history.redo(&mut grid).is_none());
+        assert_eq!(row(&grid), [1, 0, 3]);
+
+        history.undo(&mut grid);
+        history.undo(&mut grid);
+        assert_eq!(row(&grid), [0, 0, 0]);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn groups_undo_as_one_edit() {
+        let mut grid = Grid::new(3, 1);
+        let mut history = EditHistory::default();
+        paint(&mut grid, &mut history, 0, 1);
+        history.begin_group();
+        paint(&mut grid, &mut history, 1, 2);
+        paint(&mut grid, &mut history, 1, 3);
+        paint(&mut grid, &mut history, 2, 4);
+
+        // undoing closes the open group first
+        assert_eq!(history.undo(&mut grid).map(|edit| edit.changes().len()), Some(3));
+        assert_eq!(row(&grid), [1, 0, 0]);
+        history.redo(&mut grid);
+        assert_eq!(row(&grid), [1, 3, 4]);
+    }
+
+    #[test]
+    fn only_the_last_edits_within_the_limit_are_kept() {
+        let mut grid = Grid::new(3, 1);
+        let mut history = EditHistory::with_limit(2);
+        for x in 0..3 {
+            paint(&mut grid, &mut history, x, 5);
+        }
+        while history.undo(&mut grid).is_some() {}
+        assert_eq!(row(&grid), [5, 0, 0]);
+    }
+}
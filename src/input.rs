@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 
 use crate::{GridDimensions, MainCamera};
+use crate::editor::EditorState;
 
 pub struct GameInputPlugin;
 
@@ -10,7 +11,7 @@ impl Plugin for GameInputPlugin {
             .insert_resource(PlayerCursor::default())
             .add_systems(PreUpdate, update_player_cursor)
             .add_systems(Update, mess_with_camera)
-            .add_systems(Update, mouse_picking)
+            .add_systems(Update, mouse_picking.run_if(in_state(EditorState::Playing)))
         ;
     }
 }
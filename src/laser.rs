@@ -4,6 +4,7 @@ use bevy::prelude::*;
 use bevy::sprite::Anchor;
 use bevy_rapier2d::prelude::{QueryFilter, RapierContext};
 
+use crate::editor::EditorState;
 use crate::input::PlayerCursor;
 use crate::PlayerControlled;
 
@@ -12,7 +13,8 @@ pub struct LasersPlugin;
 impl Plugin for LasersPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(PreUpdate, player_laser_input)
+            .add_systems(PreUpdate, player_laser_input.run_if(in_state(EditorState::Playing)))
+            .add_systems(OnEnter(EditorState::Editing), switch_off_lasers)
             .add_systems(Update, solve_laser_impacts)
             .add_systems(Update, sync_laser_sprites.after(solve_laser_impacts))
         ;
@@ -71,6 +73,17 @@ fn player_laser_input(
     }
 }
 
+// the left mouse button paints in the editor, so the laser shouldn't stay stuck on
+fn switch_off_lasers(
+    mut lasers: Query<&mut Laser>,
+) {
+    for mut laser in lasers.iter_mut() {
+        laser.origin = None;
+        laser.direction = None;
+        laser.impact_distance = None;
+    }
+}
+
 fn solve_laser_impacts(
     mut lasers: Query<(Entity, &mut Laser)>,
     rapier_context: Res<RapierContext>,
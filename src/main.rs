@@ -1,70 +1,125 @@
 #![feature(step_trait)]
 
+use std::collections::{HashMap, HashSet};
+
 use bevy::{
     prelude::*,
     render::camera::ScalingMode,
 };
+use bevy::render::mesh::{Indices, PrimitiveTopology};
 use bevy::sprite::Anchor;
 use bevy_rapier2d::prelude::*;
 
-use crate::border::{Border, collect_borders};
+use crate::border::{Border, BorderSegment, Edge, collect_border_segments, collect_borders_around, merge_borders};
+use crate::contour::{Contour, trace_contours};
+use crate::editor::{EditorPlugin, EditorState};
 use crate::fill::flood_fill;
 use crate::grid::*;
 use crate::input::{GameInputPlugin, PlayerCursor};
 use crate::laser::{LaserBundle, LasersPlugin};
+use crate::marching::{Polygon, Smoothing};
 use crate::noise::Noise;
 use crate::pathing::PathingPlugin;
+use crate::procgen::{IslandConfig, SEA_LEVEL, sample_island_elevation};
+use crate::streaming::WorldStreamingPlugin;
+use crate::tiled::PointOfInterest;
 use crate::wiggle::{TileWiggle, TileWigglePlugin};
 use crate::zone::*;
 
+mod bit_grid;
 mod border;
 mod cardinal;
+mod chunked;
+mod contour;
+mod distance;
+mod editor;
 mod fill;
 mod grid;
+mod grid_image;
+mod history;
 mod input;
 mod laser;
+mod marching;
+mod morphology;
 mod noise;
 mod pathing;
 mod procgen;
+mod save;
+mod streaming;
+mod tiled;
 mod wiggle;
 mod zone;
 
 fn main() {
-    App::new()
+    let mut app = App::new();
+    app
         .add_plugins(DefaultPlugins)
         .add_plugins(GameInputPlugin)
-        .add_plugins(ZonePlugin(50, 50))
-        // .add_plugin(TileWigglePlugin)
         .add_systems(Startup, setup_camera)
-        .add_systems(Update, reset_tiles_on_keypress)
-        .add_systems(Update, sync_zone_tile_sprites)
 
         .add_plugins(LasersPlugin)
 
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_systems(Update, spawn_balls)
+        .add_systems(Update, spawn_balls.run_if(in_state(EditorState::Playing)))
         .add_systems(PostUpdate, reap_balls)
+    ;
+
+    // `--stream` swaps the fixed-size zone (and everything that edits it) for an endless streamed world
+    if std::env::args().any(|arg| arg == "--stream") {
+        app
+            .add_state::<EditorState>()
+            .add_plugins(WorldStreamingPlugin::default());
+    } else {
+        app
+            .add_plugins(EditorPlugin)
+            .add_plugins(ZonePlugin(50, 50))
+            // .add_plugin(TileWigglePlugin)
+            .add_systems(Update, fit_camera_to_zone.in_set(ZoneSystems::Settle))
+            .add_systems(Update, reset_tiles_on_keypress)
+            .add_systems(Update, save_load_zone_on_keypress)
+            .add_systems(Update, resize_zone_on_keypress)
+            .add_systems(Update, export_zone_png_on_keypress)
+            .add_systems(Update, export_zone_tiled_on_keypress)
+            .insert_resource(WallColliders::default())
+            .insert_resource(CoastStyle::default())
+            .add_systems(Update, toggle_wall_colliders_on_keypress)
+            .add_systems(Update, (
+                sync_zone_tile_sprites,
+                sync_zone_border_walls,
+                sync_zone_contour_walls,
+                sync_zone_smoothed_coast,
+            ).in_set(ZoneSystems::Settle))
+            .add_systems(Update, draw_contour_walls)
 
-        .add_plugins(PathingPlugin)
-        .add_systems(Startup, init_player)
-        .add_systems(Update, handle_player_collisions)
-        .run();
+            .add_plugins(PathingPlugin)
+            .add_systems(Startup, init_player)
+            .add_systems(Update, fit_player_to_tiles.in_set(ZoneSystems::Settle))
+            .add_systems(Update, relocate_player_after_regenerate.in_set(ZoneSystems::Settle))
+            .add_systems(Update, handle_player_collisions);
+    }
+    app.run();
 }
 
 #[derive(Component)]
 struct PlayerControlled;
 
+// in tiles
+const PLAYER_SIZE: f32 = 2.;
+const BALL_RADIUS: f32 = 0.25;
+
 fn init_player(
     mut commands: Commands,
+    dimensions: Res<GridDimensions>,
 ) {
+    let size = PLAYER_SIZE * dimensions.tile_size;
     commands
         .spawn(PlayerControlled)
         .insert(LaserBundle::default())
-        .insert(Collider::cuboid(1., 1.))
+        .insert(Collider::cuboid(size * 0.5, size * 0.5))
         .insert(SpriteBundle {
             sprite: Sprite {
                 color: Color::BLACK,
-                custom_size: Some(Vec2::splat(2.)),
+                custom_size: Some(Vec2::splat(size)),
                 anchor: Anchor::Center,
                 ..default()
             },
@@ -78,6 +133,47 @@ fn init_player(
     ;
 }
 
+// the player outlives the zone, so it has to keep up when a new one has different-sized tiles
+fn fit_player_to_tiles(
+    dimensions: Res<GridDimensions>,
+    mut players: Query<(&mut Collider, &mut Sprite), With<PlayerControlled>>,
+) {
+    if !dimensions.is_changed() {
+        return;
+    }
+    let size = PLAYER_SIZE * dimensions.tile_size;
+    for (mut collider, mut sprite) in &mut players {
+        *collider = Collider::cuboid(size * 0.5, size * 0.5);
+        sprite.custom_size = Some(Vec2::splat(size));
+    }
+}
+
+// a new zone may have put water (or nothing at all) under the player
+fn relocate_player_after_regenerate(
+    mut regenerated: EventReader<ZoneRegenerated>,
+    zone: Res<Grid<TileState>>,
+    dimensions: Res<GridDimensions>,
+    mut players: Query<&mut Transform, With<PlayerControlled>>,
+) {
+    if regenerated.iter().count() == 0 {
+        return;
+    }
+    for mut transform in &mut players {
+        let standing_on = dimensions.position_to_address(transform.translation.truncate());
+        if standing_on.is_some_and(|addr| zone[addr].is_floor()) {
+            continue;
+        }
+        let target = match standing_on {
+            Some(addr) => zone.nearest_where(addr, TileState::is_floor),
+            None => spawn_point(&zone, &dimensions),
+        };
+        if let Some(target) = target {
+            let xy = dimensions.world_pos_of(&target) + Vec2::splat(dimensions.tile_size * 0.5);
+            transform.translation = (xy, transform.translation.z).into();
+        }
+    }
+}
+
 #[derive(Component)]
 struct MainCamera;
 
@@ -99,74 +195,408 @@ fn setup_camera(
     }).insert(MainCamera);
 }
 
+fn fit_camera_to_zone(
+    dimensions: Res<GridDimensions>,
+    mut cameras: Query<(&mut OrthographicProjection, &mut Transform), With<MainCamera>>,
+) {
+    if !dimensions.is_changed() {
+        return;
+    }
+    for (mut projection, mut transform) in &mut cameras {
+        projection.scaling_mode = ScalingMode::AutoMin {
+            min_width: dimensions.world_width(),
+            min_height: dimensions.world_height(),
+        };
+        transform.translation = (dimensions.world_center(), transform.translation.z).into();
+    }
+}
+
 #[derive(Component)]
 struct BorderWall;
 
+/// How the zone's walls get their colliders
+#[derive(Resource, Default, Copy, Clone, Debug, Eq, PartialEq)]
+enum WallColliders {
+    /// A box per straight run of border, updated only around edited tiles
+    #[default]
+    Segments,
+    /// A polyline around each floor region and each hole in one, so things slide along
+    /// the coast without catching on the seams between boxes. Retraced in full on any edit,
+    /// so it's slower to keep up with editing than `Segments`.
+    Contours,
+    /// A polyline around each smoothed outline from marching squares, which also
+    /// gets drawn as a filled mesh in place of the tiles; see `CoastStyle`
+    Smoothed,
+}
+
+/// How the outlines for `WallColliders::Smoothed` are made
+#[derive(Resource, Copy, Clone, Debug)]
+struct CoastStyle {
+    /// Follow the generator's continuous elevation where it agrees with the tiles,
+    /// rather than cutting each tile corner at 45 degrees
+    from_elevation: bool,
+    smoothing: Smoothing,
+}
+
+impl Default for CoastStyle {
+    fn default() -> Self {
+        CoastStyle {
+            from_elevation: true,
+            smoothing: Smoothing::Chaikin { iterations: 2 },
+        }
+    }
+}
+
+/// A wall following a traced contour, in world coordinates
+#[derive(Component)]
+struct ContourWall(Vec<Vec2>);
+
 fn sync_zone_tile_sprites(
     dimensions: Res<GridDimensions>,
     zone: Res<Grid<TileState>>,
-    mut sprites: Query<(&mut Sprite, &TileAddress)>,
-    border_entities: Query<Entity, (With<Border>, With<BorderWall>)>,
+    mut tile_changed: EventReader<TileChanged>,
+    wall_colliders: Res<WallColliders>,
+    mut sprite_index: Local<HashMap<TileAddress, Entity>>,
+    mut sprites: Query<(&mut Sprite, &mut TileState, &mut Visibility)>,
     mut commands: Commands,
 ) {
+    // the smoothed coast gets drawn instead of the tiles
+    let visibility = match *wall_colliders {
+        WallColliders::Smoothed => Visibility::Hidden,
+        _ => Visibility::Inherited,
+    };
+
     if zone.is_added() {
+        // a freshly loaded zone replaces all of the previous zone's sprites
+        tile_changed.clear();
+        for (_, entity) in sprite_index.drain() {
+            commands.entity(entity).despawn();
+        }
+
         for tile_address in zone.addresses() {
             let pos = dimensions.world_pos_of(&tile_address);
             let tile_state = zone[tile_address];
 
-            commands
+            let entity = commands
                 .spawn(SpriteBundle {
                     sprite: Sprite {
-                        custom_size: Some(Vec2::ONE),
+                        custom_size: Some(Vec2::splat(dimensions.tile_size)),
                         anchor: Anchor::BottomLeft,
                         color: tile_state.as_color(),
                         ..default()
                     },
                     transform: Transform::from_translation((pos, 0.).into()),
+                    visibility,
                     ..default()
                 })
                 .insert(tile_address)
                 .insert(zone[tile_address])
                 .insert(TileWiggle::new())
-            ;
+                .id();
+            sprite_index.insert(tile_address, entity);
         }
-    } else if zone.is_changed() {
-        for (mut sprite, tile_address) in &mut sprites {
-            sprite.color = zone[tile_address].as_color();
+    } else {
+        // changes that went through the zone's journal say exactly which sprites to update
+        let mut journaled = false;
+        for change in tile_changed.iter() {
+            journaled = true;
+            let Some(&entity) = sprite_index.get(&change.address) else { continue };
+            if let Ok((mut sprite, mut tile_state, _)) = sprites.get_mut(entity) {
+                sprite.color = change.after.as_color();
+                *tile_state = change.after;
+            }
+        }
+
+        // but the grid can also be written directly, without a journal entry;
+        // then the only way to catch up is to check every sprite against it
+        if zone.is_changed() && !journaled {
+            for (&address, &entity) in sprite_index.iter() {
+                let Some(&after) = zone.tile_at(&address) else { continue };
+                if let Ok((mut sprite, mut tile_state, _)) = sprites.get_mut(entity) {
+                    if *tile_state != after {
+                        sprite.color = after.as_color();
+                        *tile_state = after;
+                    }
+                }
+            }
+        }
+
+        if wall_colliders.is_changed() {
+            for &entity in sprite_index.values() {
+                if let Ok((_, _, mut sprite_visibility)) = sprites.get_mut(entity) {
+                    *sprite_visibility = visibility;
+                }
+            }
         }
     }
+}
 
-    if zone.is_added() || zone.is_changed() {
-        for entity in &border_entities {
+/// Which wall entity covers each border, so walls can be updated around edited tiles
+#[derive(Default)]
+struct WallIndex {
+    edges: HashMap<Edge, Entity>,
+    segments: HashMap<Entity, BorderSegment>,
+}
+
+impl WallIndex {
+    fn insert(&mut self, entity: Entity, segment: BorderSegment) {
+        self.edges.extend(segment.borders().map(|border| (*border.edge(), entity)));
+        self.segments.insert(entity, segment);
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<BorderSegment> {
+        let segment = self.segments.remove(&entity)?;
+        for border in segment.borders() {
+            self.edges.remove(border.edge());
+        }
+        Some(segment)
+    }
+}
+
+fn sync_zone_border_walls(
+    dimensions: Res<GridDimensions>,
+    zone: Res<Grid<TileState>>,
+    wall_colliders: Res<WallColliders>,
+    mut tile_changed: EventReader<TileChanged>,
+    mut wall_index: Local<WallIndex>,
+    mut commands: Commands,
+) {
+    let is_floor = |tile: &TileState| *tile == TileState::Floor;
+    let dirty: Vec<TileAddress> = tile_changed.iter().map(|change| change.address).collect();
+
+    // a grid written without a journal entry doesn't say which walls went stale, so they all do
+    let unjournaled = zone.is_changed() && dirty.is_empty();
+    if zone.is_added() || wall_colliders.is_changed() || unjournaled {
+        for (entity, _) in wall_index.segments.drain() {
             commands.entity(entity).despawn();
         }
+        wall_index.edges.clear();
+        if *wall_colliders != WallColliders::Segments {
+            return;
+        }
+        for segment in collect_border_segments(&*zone, &is_floor) {
+            wall_index.insert(spawn_border_wall(&mut commands, &dimensions, segment), segment);
+        }
+        return;
+    }
 
-        collect_borders(
-            &zone,
-            &|tile: &TileState| *tile == TileState::Floor,
-            &mut |border: Border| {
-                let aabb = border.get_aabb(&dimensions, 0.1);
-                let center = aabb.center(); //mins;
-                let size: [f32; 2] = aabb.extents().into();
-
-                commands
-                    .spawn(SpriteBundle {
-                        sprite: Sprite {
-                            anchor: Anchor::Center,
-                            color: Color::CYAN,
-                            custom_size: Some(size.into()),
-                            ..default()
-                        },
-                        transform: Transform::from_translation((center.x, center.y, 0.).into()),
-                        ..default()
-                    })
-                    .insert(border)
-                    .insert(BorderWall)
-                    .insert(RigidBody::Fixed)
-                    .insert(Collider::cuboid(size[0] * 0.5, size[1] * 0.5))
-                ;
-            }
-        );
+    if dirty.is_empty() || *wall_colliders != WallColliders::Segments {
+        return;
+    }
+
+    // every wall touching a dirty tile gets replaced, but only those; walls elsewhere
+    // (and their colliders) are left alone, at the cost of not re-merging with them
+    let touched: HashSet<Entity> = dirty.iter()
+        .flat_map(|addr| Edge::around(*addr))
+        .filter_map(|edge| wall_index.edges.get(&edge).copied())
+        .collect();
+    let mut retrace = dirty;
+    for entity in touched {
+        commands.entity(entity).despawn();
+        if let Some(segment) = wall_index.remove(entity) {
+            retrace.extend(segment.borders().map(|border| border.inside_tile()));
+        }
+    }
+
+    let mut borders = Vec::new();
+    collect_borders_around(&*zone, retrace, &is_floor, &mut |border: Border| {
+        // the retraced tiles can also touch walls that weren't replaced
+        if !wall_index.edges.contains_key(border.edge()) {
+            borders.push(border);
+        }
+    });
+
+    for segment in merge_borders(borders) {
+        wall_index.insert(spawn_border_wall(&mut commands, &dimensions, segment), segment);
+    }
+}
+
+fn spawn_border_wall(commands: &mut Commands, dimensions: &GridDimensions, segment: BorderSegment) -> Entity {
+    let aabb = segment.get_aabb(dimensions, 0.1);
+    let center = aabb.center(); //mins;
+    let size: [f32; 2] = aabb.extents().into();
+
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                anchor: Anchor::Center,
+                color: Color::CYAN,
+                custom_size: Some(size.into()),
+                ..default()
+            },
+            transform: Transform::from_translation((center.x, center.y, 0.).into()),
+            ..default()
+        })
+        .insert(segment)
+        .insert(BorderWall)
+        .insert(RigidBody::Fixed)
+        .insert(Collider::cuboid(size[0] * 0.5, size[1] * 0.5))
+        .id()
+}
+
+fn sync_zone_contour_walls(
+    dimensions: Res<GridDimensions>,
+    zone: Res<Grid<TileState>>,
+    wall_colliders: Res<WallColliders>,
+    mut tile_changed: EventReader<TileChanged>,
+    mut walls: Local<Vec<Entity>>,
+    mut commands: Commands,
+) {
+    // any change to the grid counts, even one that bypassed the journal
+    let edited = tile_changed.iter().count() > 0 || zone.is_changed();
+    if !(zone.is_added() || wall_colliders.is_changed() || edited) {
+        return;
+    }
+
+    for entity in walls.drain(..) {
+        commands.entity(entity).despawn();
+    }
+    if *wall_colliders != WallColliders::Contours {
+        return;
+    }
+
+    for contour in trace_contours(&*zone, &|tile: &TileState| tile.is_floor()) {
+        walls.push(spawn_contour_wall(&mut commands, &dimensions, &contour));
+    }
+}
+
+fn spawn_contour_wall(commands: &mut Commands, dimensions: &GridDimensions, contour: &Contour) -> Entity {
+    let points = contour.world_points(dimensions);
+    commands
+        .spawn(TransformBundle::default())
+        .insert(BorderWall)
+        .insert(RigidBody::Fixed)
+        .insert(Collider::polyline(points.clone(), Some(contour.closed_indices())))
+        .insert(ContourWall(points))
+        .id()
+}
+
+fn draw_contour_walls(
+    walls: Query<&ContourWall>,
+    mut gizmos: Gizmos,
+) {
+    for ContourWall(points) in &walls {
+        let closed = points.iter().copied().chain(points.first().copied());
+        gizmos.linestrip_2d(closed, Color::CYAN);
+    }
+}
+
+/// What `sync_zone_smoothed_coast` has spawned, so the next rebuild can take it down again
+#[derive(Default)]
+struct SmoothedCoast {
+    entities: Vec<Entity>,
+    meshes: Vec<Handle<Mesh>>,
+    // the same two colors every time, so they're only added once
+    materials: Option<[Handle<ColorMaterial>; 2]>,
+}
+
+fn sync_zone_smoothed_coast(
+    dimensions: Res<GridDimensions>,
+    zone: Res<Grid<TileState>>,
+    zone_noise: Res<ZoneNoise>,
+    config: Res<IslandConfig>,
+    wall_colliders: Res<WallColliders>,
+    coast_style: Res<CoastStyle>,
+    mut tile_changed: EventReader<TileChanged>,
+    mut coast: Local<SmoothedCoast>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut commands: Commands,
+) {
+    // any change to the grid counts, even one that bypassed the journal
+    let edited = tile_changed.iter().count() > 0 || zone.is_changed();
+    if !(zone.is_added() || wall_colliders.is_changed() || coast_style.is_changed() || edited) {
+        return;
+    }
+
+    for entity in coast.entities.drain(..) {
+        commands.entity(entity).despawn();
+    }
+    for mesh in coast.meshes.drain(..) {
+        meshes.remove(mesh);
+    }
+    if *wall_colliders != WallColliders::Smoothed {
+        return;
+    }
+
+    let is_floor = |tile: &TileState| tile.is_floor();
+    let field = if coast_style.from_elevation {
+        let mut elevation = sample_island_elevation(&dimensions, zone_noise.noise(), &config);
+        marching::constrain_to_tiles(&mut elevation, SEA_LEVEL, &zone, &is_floor);
+        elevation
+    } else {
+        marching::tile_field(&zone, &is_floor)
+    };
+    let iso = if coast_style.from_elevation { SEA_LEVEL } else { 0.5 };
+
+    // the tile sprites are hidden meanwhile, so the water needs a backdrop.
+    // All of this sits behind the tiles' depth, so it can't cover anything standing on them
+    let backdrop = commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: TileState::Water.as_color(),
+                custom_size: Some(Vec2::new(dimensions.world_width(), dimensions.world_height())),
+                anchor: Anchor::BottomLeft,
+                ..default()
+            },
+            transform: Transform::from_translation((dimensions.bottom_left, -0.3).into()),
+            ..default()
+        })
+        .id();
+    coast.entities.push(backdrop);
+
+    let [floor_material, water_material] = coast.materials
+        .get_or_insert_with(|| {
+            [TileState::Floor, TileState::Water].map(|tile| materials.add(ColorMaterial::from(tile.as_color())))
+        })
+        .clone();
+    let polygons: Vec<Polygon> = marching::march_squares(&field, iso, &dimensions)
+        .into_iter()
+        .map(|polygon| Polygon {
+            points: marching::smooth(&polygon.points, coast_style.smoothing),
+            is_hole: polygon.is_hole,
+        })
+        .collect();
+    for (i, polygon) in polygons.iter().enumerate() {
+        // holes aren't cut out of the mesh around them, so each outline is drawn above the ones it's nested in
+        let depth = polygons.iter()
+            .enumerate()
+            .filter(|&(j, other)| j != i && other.contains(polygon.points[0]))
+            .count();
+        let material = if polygon.is_hole { water_material.clone() } else { floor_material.clone() };
+        let z = -0.2 + 0.01 * depth as f32;
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        let positions: Vec<[f32; 3]> = polygon.points.iter().map(|p| [p.x, p.y, 0.]).collect();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_indices(Some(Indices::U32(polygon.triangulate().concat())));
+        let mesh = meshes.add(mesh);
+        coast.meshes.push(mesh.clone());
+
+        let entity = commands
+            .spawn(ColorMesh2dBundle {
+                mesh: mesh.into(),
+                material,
+                transform: Transform::from_translation((0., 0., z).into()),
+                ..default()
+            })
+            .insert(BorderWall)
+            .insert(RigidBody::Fixed)
+            .insert(Collider::polyline(polygon.points.clone(), Some(polygon.closed_indices())))
+            .id();
+        coast.entities.push(entity);
+    }
+}
+
+fn toggle_wall_colliders_on_keypress(
+    keyboard: Res<Input<KeyCode>>,
+    mut wall_colliders: ResMut<WallColliders>,
+) {
+    if keyboard.just_pressed(KeyCode::F4) {
+        *wall_colliders = match *wall_colliders {
+            WallColliders::Segments => WallColliders::Contours,
+            WallColliders::Contours => WallColliders::Smoothed,
+            WallColliders::Smoothed => WallColliders::Segments,
+        };
     }
 }
 
@@ -179,6 +609,79 @@ fn reset_tiles_on_keypress(
     }
 }
 
+fn resize_zone_on_keypress(
+    keyboard: Res<Input<KeyCode>>,
+    dimensions: Res<GridDimensions>,
+    mut zone_commands: EventWriter<ZoneCommand>,
+) {
+    let step = if keyboard.just_pressed(KeyCode::Equals) {
+        10
+    } else if keyboard.just_pressed(KeyCode::Minus) {
+        -10
+    } else {
+        return;
+    };
+    let [w, h] = dimensions.size_in_tiles;
+    zone_commands.send(ZoneCommand::Reconfigure {
+        size: [w.saturating_add_signed(step).max(10), h.saturating_add_signed(step).max(10)],
+        tile_size: dimensions.tile_size,
+        bottom_left: dimensions.bottom_left,
+    });
+}
+
+fn save_load_zone_on_keypress(
+    keyboard: Res<Input<KeyCode>>,
+    mut zone_commands: EventWriter<ZoneCommand>,
+) {
+    if keyboard.just_pressed(KeyCode::F5) {
+        zone_commands.send(ZoneCommand::Save("zone.gzon".into()));
+    }
+    if keyboard.just_pressed(KeyCode::F9) {
+        zone_commands.send(ZoneCommand::Load("zone.gzon".into()));
+    }
+}
+
+fn export_zone_png_on_keypress(
+    keyboard: Res<Input<KeyCode>>,
+    zone: Res<Grid<TileState>>,
+) {
+    if keyboard.just_pressed(KeyCode::F12) {
+        match grid_image::save_png(&zone, TileState::as_color, ZONE_PNG_SCALE, "zone.png") {
+            Ok(()) => println!("exported zone to zone.png"),
+            Err(e) => println!("failed to export zone: {}", e),
+        }
+    }
+}
+
+fn export_zone_tiled_on_keypress(
+    keyboard: Res<Input<KeyCode>>,
+    zone: Res<Grid<TileState>>,
+    dimensions: Res<GridDimensions>,
+    players: Query<&GlobalTransform, With<PlayerControlled>>,
+) {
+    if keyboard.just_pressed(KeyCode::F11) {
+        let points_of_interest: Vec<PointOfInterest> = players
+            .iter()
+            .map(|transform| PointOfInterest {
+                name: "player".to_string(),
+                pos: transform.translation().truncate(),
+            })
+            .collect();
+        let result = tiled::export_zone(
+            "zone.tmj",
+            &zone,
+            &dimensions,
+            &TileState::gid_mapping(),
+            &|tile: &TileState| *tile == TileState::Floor,
+            &points_of_interest,
+        );
+        match result {
+            Ok(()) => println!("exported zone to zone.tmj"),
+            Err(e) => println!("failed to export zone: {}", e),
+        }
+    }
+}
+
 #[derive(Component)]
 struct Ball;
 
@@ -186,18 +689,21 @@ fn spawn_balls(
     mut commands: Commands,
     buttons: Res<Input<MouseButton>>,
     cursor: Res<PlayerCursor>,
+    dimensions: Res<GridDimensions>,
 ) {
     if buttons.pressed(MouseButton::Right) {
+        let radius = BALL_RADIUS * dimensions.tile_size;
         commands
             .spawn(Ball)
             .insert(RigidBody::Dynamic)
-            .insert(Collider::ball(0.25))
+            .insert(Collider::ball(radius))
             .insert(Restitution::coefficient(0.7))
             .insert(ColliderMassProperties::Density(0.1))
             .insert(Ccd::enabled())
+            .insert(ZoneScoped)
             .insert(SpriteBundle {
                 sprite: Sprite {
-                    custom_size: Some(Vec2::new(0.5, 0.5)),
+                    custom_size: Some(Vec2::splat(radius * 2.)),
                     anchor: Anchor::Center,
                     color: Color::CYAN,
                     ..default()
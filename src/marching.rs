@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Vec2;
+
+use crate::grid::{Grid, TileAddress};
+use crate::zone::GridDimensions;
+
+/// A closed outline produced by `march_squares`, in world coordinates.
+#[derive(Clone, Debug)]
+pub struct Polygon {
+    /// Outer boundaries wind counter-clockwise (inside on the left);
+    /// the boundaries of holes wind clockwise.
+    pub points: Vec<Vec2>,
+    pub is_hole: bool,
+}
+
+impl Polygon {
+    /// Index pairs for the polygon's edges, including the one that closes the loop,
+    /// in the form `Collider::polyline` wants them
+    pub fn closed_indices(&self) -> Vec<[u32; 2]> {
+        let n = self.points.len() as u32;
+        (0..n).map(|i| [i, (i + 1) % n]).collect()
+    }
+
+    /// Whether `point` is inside the outline, regardless of its winding
+    pub fn contains(&self, point: Vec2) -> bool {
+        let n = self.points.len();
+        let mut inside = false;
+        for i in 0..n {
+            let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+            if (a.y > point.y) != (b.y > point.y) {
+                let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                if point.x < x {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    /// Triangles covering the polygon, as indices into `points`. Holes aren't cut out of their
+    /// surrounding polygon; draw them on top instead.
+    pub fn triangulate(&self) -> Vec<[u32; 3]> {
+        if self.points.len() < 3 {
+            return Vec::new();
+        }
+        if self.is_hole {
+            let mut reversed = self.points.clone();
+            reversed.reverse();
+            let last = reversed.len() as u32 - 1;
+            triangulate_ccw(&reversed)
+                .into_iter()
+                .map(|[a, b, c]| [last - a, last - b, last - c])
+                .collect()
+        } else {
+            triangulate_ccw(&self.points)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Smoothing {
+    None,
+    /// Corner cutting; each iteration doubles the number of points and rounds off corners
+    Chaikin { iterations: u32 },
+    /// A spline through the original points, with this many points per original edge
+    CatmullRom { subdivisions: u32 },
+}
+
+/// A scalar field with one sample per tile, where the tiles that pass `test_inside` are 1 and the rest are 0.
+/// Marching it at 0.5 gives outlines that cut the corners of the tiles diagonally.
+pub fn tile_field<T, F>(grid: &Grid<T>, test_inside: &F) -> Grid<f32>
+    where F: Fn(&T) -> bool
+{
+    let mut field = Grid::new(grid.width(), grid.height());
+    for addr in grid.addresses() {
+        field[addr] = if test_inside(&grid[addr]) { 1.0 } else { 0.0 };
+    }
+    field
+}
+
+/// Clamps a continuous `field` (like the generator's elevation) to the right side of `iso` wherever it
+/// disagrees with the tiles, e.g. where generation dropped a small island or the zone has been edited.
+/// Outlines then follow the tiles, but keep the field's smooth shape wherever the two agree.
+pub fn constrain_to_tiles<T, F>(field: &mut Grid<f32>, iso: f32, grid: &Grid<T>, test_inside: &F)
+    where F: Fn(&T) -> bool
+{
+    // far enough from `iso` that the crossing stays within the disagreeing tile
+    const MARGIN: f32 = 0.05;
+    for addr in grid.addresses() {
+        if let Some(value) = field.tile_at_mut(&addr) {
+            *value = if test_inside(&grid[addr]) {
+                value.max(iso + MARGIN)
+            } else {
+                value.min(iso - MARGIN)
+            };
+        }
+    }
+}
+
+/// Traces the outlines of the parts of `field` above `iso`, with one sample at the center of each tile of `dims`.
+///
+/// Beyond the edges of the field counts as below `iso`, so every outline is closed.
+/// Where two diagonal samples are above `iso` and the other two are below, the average
+/// of the four decides whether the parts are joined.
+pub fn march_squares(field: &Grid<f32>, iso: f32, dims: &GridDimensions) -> Vec<Polygon> {
+    let (width, height) = (field.width() as i64, field.height() as i64);
+    let outside = field.addresses().map(|addr| field[addr]).fold(iso - 1.0, f32::min);
+    let sample = |x: i64, y: i64| -> f32 {
+        if x < 0 || y < 0 || x >= width || y >= height {
+            outside
+        } else {
+            field[TileAddress(x as u32, y as u32)]
+        }
+    };
+
+    // each crossing is named by the sample-grid edge it's on: the lower sample, and whether the edge is vertical
+    let mut crossings: HashMap<(i64, i64, bool), Vec2> = HashMap::new();
+    let mut next: HashMap<(i64, i64, bool), (i64, i64, bool)> = HashMap::new();
+
+    for y in -1..height {
+        for x in -1..width {
+            // corners and edges of the cell, counter-clockwise from the bottom left
+            let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+            let values = corners.map(|(cx, cy)| sample(cx, cy));
+            let edges = [(x, y, false), (x + 1, y, true), (x, y + 1, false), (x, y, true)];
+
+            // an edge "exits" the inside if it goes from above iso to below, counter-clockwise around the cell
+            let mut exits = Vec::new();
+            let mut enters = Vec::new();
+            for i in 0..4 {
+                let j = (i + 1) % 4;
+                let (a, b) = (values[i], values[j]);
+                let (a_in, b_in) = (a > iso, b > iso);
+                if a_in == b_in {
+                    continue;
+                }
+                let t = (iso - a) / (b - a);
+                let from = Vec2::new(corners[i].0 as f32, corners[i].1 as f32);
+                let to = Vec2::new(corners[j].0 as f32, corners[j].1 as f32);
+                crossings.entry(edges[i]).or_insert_with(|| from.lerp(to, t));
+                if a_in { exits.push(i) } else { enters.push(i) }
+            }
+
+            // walking from an exit to an enter keeps the inside on the left. Normally that means pairing
+            // each exit with the enter before it; in a joined saddle, with the one after it instead
+            let joined = values.iter().sum::<f32>() * 0.25 > iso;
+            for &exit in &exits {
+                let step = if exits.len() == 2 && joined { 1 } else { 3 };
+                let enter = (1..4)
+                    .map(|k| (exit + k * step) % 4)
+                    .find(|i| enters.contains(i))
+                    .unwrap();
+                next.insert(edges[exit], edges[enter]);
+            }
+        }
+    }
+
+    let to_world = |p: Vec2| dims.bottom_left + (p + Vec2::splat(0.5)) * dims.tile_size;
+    let mut polygons = Vec::new();
+    while let Some(&start) = next.keys().next() {
+        let mut points = Vec::new();
+        let mut edge = start;
+        while let Some(following) = next.remove(&edge) {
+            points.push(to_world(crossings[&edge]));
+            edge = following;
+        }
+        let is_hole = signed_area(&points) < 0.0;
+        polygons.push(Polygon { points, is_hole });
+    }
+    polygons
+}
+
+/// Smooths a closed outline, keeping its winding.
+pub fn smooth(points: &[Vec2], smoothing: Smoothing) -> Vec<Vec2> {
+    match smoothing {
+        Smoothing::None => points.to_vec(),
+        Smoothing::Chaikin { iterations } => {
+            let mut points = points.to_vec();
+            for _ in 0..iterations {
+                let n = points.len();
+                points = (0..n)
+                    .flat_map(|i| {
+                        let (a, b) = (points[i], points[(i + 1) % n]);
+                        [a.lerp(b, 0.25), a.lerp(b, 0.75)]
+                    })
+                    .collect();
+            }
+            points
+        }
+        Smoothing::CatmullRom { subdivisions } => {
+            let n = points.len();
+            let subdivisions = subdivisions.max(1);
+            let mut out = Vec::with_capacity(n * subdivisions as usize);
+            for i in 0..n {
+                let p0 = points[(i + n - 1) % n];
+                let p1 = points[i];
+                let p2 = points[(i + 1) % n];
+                let p3 = points[(i + 2) % n];
+                for s in 0..subdivisions {
+                    let t = s as f32 / subdivisions as f32;
+                    let (t2, t3) = (t * t, t * t * t);
+                    out.push(0.5 * (
+                        2.0 * p1
+                            + (p2 - p0) * t
+                            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3
+                    ));
+                }
+            }
+            out
+        }
+    }
+}
+
+fn signed_area(points: &[Vec2]) -> f32 {
+    let n = points.len();
+    (0..n).map(|i| points[i].perp_dot(points[(i + 1) % n])).sum::<f32>() * 0.5
+}
+
+// ear clipping, for a simple polygon wound counter-clockwise
+fn triangulate_ccw(points: &[Vec2]) -> Vec<[u32; 3]> {
+    let mut remaining: Vec<u32> = (0..points.len() as u32).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    let at = |i: u32| points[i as usize];
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (pa, pb, pc) = (at(a), at(b), at(c));
+            // the corner has to be convex, with no other remaining point inside the triangle
+            (pb - pa).perp_dot(pc - pb) > 0.0 && remaining.iter()
+                .filter(|&&j| j != a && j != b && j != c)
+                .all(|&j| !in_triangle(at(j), pa, pb, pc))
+        });
+        // degenerate (e.g. collinear) leftovers would never yield an ear; clip them anyway
+        let i = ear.unwrap_or(0);
+        triangles.push([remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]]);
+        remaining.remove(i);
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(p - a) >= 0.0 && (c - b).perp_dot(p - b) >= 0.0 && (a - c).perp_dot(p - c) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 5x5 ring of floor around a one-tile pond
+    fn ring() -> Grid<bool> {
+        let mut grid = Grid::<bool>::new(5, 5);
+        for addr in grid.addresses().collect::<Vec<_>>() {
+            let (x, y) = (addr.0, addr.1);
+            grid[addr] = (1..=3).contains(&x) && (1..=3).contains(&y) && (x, y) != (2, 2);
+        }
+        grid
+    }
+
+    fn outlines(grid: &Grid<bool>) -> Vec<Polygon> {
+        let field = tile_field(grid, &|tile: &bool| *tile);
+        march_squares(&field, 0.5, &GridDimensions::new([grid.width(), grid.height()]))
+    }
+
+    fn triangle_area(points: &[Vec2], [a, b, c]: [u32; 3]) -> f32 {
+        let (a, b, c) = (points[a as usize], points[b as usize], points[c as usize]);
+        (b - a).perp_dot(c - a) * 0.5
+    }
+
+    #[test]
+    fn outer_boundaries_wind_counter_clockwise_and_holes_clockwise() {
+        let polygons = outlines(&ring());
+        assert_eq!(polygons.len(), 2);
+        let outer = polygons.iter().find(|polygon| !polygon.is_hole).unwrap();
+        let hole = polygons.iter().find(|polygon| polygon.is_hole).unwrap();
+        assert!(signed_area(&outer.points) > 0.0);
+        assert!(signed_area(&hole.points) < 0.0);
+        assert!(outer.contains(Vec2::new(1.5, 1.5)));
+        assert!(hole.contains(Vec2::new(2.5, 2.5)));
+        assert!(!outer.contains(Vec2::new(4.5, 4.5)));
+    }
+
+    #[test]
+    fn smoothing_keeps_the_winding() {
+        for polygon in outlines(&ring()) {
+            let winding = signed_area(&polygon.points).signum();
+            for smoothing in [Smoothing::Chaikin { iterations: 2 }, Smoothing::CatmullRom { subdivisions: 3 }] {
+                let smoothed = smooth(&polygon.points, smoothing);
+                assert_eq!(signed_area(&smoothed).signum(), winding, "{:?}", smoothing);
+            }
+        }
+        let square = [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y];
+        assert_eq!(smooth(&square, Smoothing::Chaikin { iterations: 3 }).len(), 4 * 8);
+        assert_eq!(smooth(&square, Smoothing::CatmullRom { subdivisions: 4 }).len(), 4 * 4);
+    }
+
+    #[test]
+    fn triangulation_covers_the_polygon_with_n_minus_2_triangles() {
+        for polygon in outlines(&ring()) {
+            let triangles = polygon.triangulate();
+            assert_eq!(triangles.len(), polygon.points.len() - 2);
+            // holes too come out counter-clockwise, and either way they add up to the outline's area
+            let areas: Vec<f32> = triangles.iter().map(|&triangle| triangle_area(&polygon.points, triangle)).collect();
+            assert!(areas.iter().all(|&area| area >= -1e-6), "{:?}", areas);
+            assert!((areas.iter().sum::<f32>() - signed_area(&polygon.points).abs()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn triangulating_a_degenerate_polygon_gives_no_triangles() {
+        for is_hole in [false, true] {
+            for points in [vec![], vec![Vec2::ZERO], vec![Vec2::ZERO, Vec2::X]] {
+                assert!(Polygon { points, is_hole }.triangulate().is_empty());
+            }
+        }
+    }
+}
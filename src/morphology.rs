@@ -0,0 +1,180 @@
+//! Morphological operations on tile masks: growing and shrinking the set tiles of a `BitGrid`
+//! by a small shape, and cleaning up features that are too small to matter.
+
+use std::collections::VecDeque;
+
+use crate::bit_grid::BitGrid;
+use crate::grid::{Grid, TileAddress};
+
+/// The shape that `dilate` stamps around each set tile, and that `erode` requires to fit.
+/// A single tile (a radius of 0, or a size of 1) leaves the mask unchanged.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Structuring {
+    /// `size` tiles on each side. Even sizes reach one tile further south and west than north and east;
+    /// opening with a size of 2 removes everything that's only one tile wide.
+    Square { size: u32 },
+    /// A plus sign, with arms `radius` tiles long
+    Cross { radius: u32 },
+    /// The tiles whose centers are within `radius` tiles of the middle tile's center
+    Disc { radius: u32 },
+}
+
+impl Structuring {
+    /// The tiles of the shape, relative to its middle tile
+    pub fn offsets(&self) -> Vec<(i32, i32)> {
+        let square = |min: i32, max: i32| (min..=max).flat_map(move |y| (min..=max).map(move |x| (x, y)));
+        match *self {
+            Structuring::Square { size } => {
+                let min = -(size.max(1) as i32 / 2);
+                square(min, min + size.max(1) as i32 - 1).collect()
+            }
+            Structuring::Cross { radius } => {
+                let radius = radius as i32;
+                square(-radius, radius).filter(|&(x, y)| x == 0 || y == 0).collect()
+            }
+            Structuring::Disc { radius } => {
+                let radius = radius as i32;
+                square(-radius, radius).filter(|&(x, y)| x * x + y * y <= radius * radius).collect()
+            }
+        }
+    }
+}
+
+/// Grows the mask by stamping the shape around each set tile. Put the other way around, a tile is
+/// set if the shape, mirrored through its middle tile and centered on it, touches any set tile;
+/// the mirroring only matters for even-sized `Square`s, which reach further south and west.
+/// That makes it the counterpart of `erode`, so that `open` and `close` are stable: doing either
+/// one again doesn't change anything more.
+pub fn dilate(mask: &BitGrid, element: &Structuring) -> BitGrid {
+    let offsets = element.offsets();
+    let mut out = BitGrid::new(mask.width(), mask.height());
+    for addr in mask.ones() {
+        for &offset in &offsets {
+            if let Some(target) = addr + offset {
+                out.set(&target, true);
+            }
+        }
+    }
+    out
+}
+
+/// Shrinks the mask: a tile stays set only if the shape, centered on it, fits entirely within
+/// set tiles. Beyond the edges of the grid counts as set, so the mask doesn't shrink away from them.
+pub fn erode(mask: &BitGrid, element: &Structuring) -> BitGrid {
+    let offsets = element.offsets();
+    let mut out = BitGrid::new(mask.width(), mask.height());
+    for addr in mask.ones() {
+        let fits = offsets.iter().all(|&offset| match addr + offset {
+            Some(target) if mask.contains(&target) => mask.get(&target),
+            _ => true,
+        });
+        if fits {
+            out.set(&addr, true);
+        }
+    }
+    out
+}
+
+/// Erodes then dilates, removing the parts of the mask that the shape can't fit into
+/// (thin spits, one-tile bumps, diagonal pinch points) while leaving the rest as it was.
+pub fn open(mask: &BitGrid, element: &Structuring) -> BitGrid {
+    dilate(&erode(mask, element), element)
+}
+
+/// Dilates then erodes, filling in the gaps and notches that the shape can't fit into.
+pub fn close(mask: &BitGrid, element: &Structuring) -> BitGrid {
+    erode(&dilate(mask, element), element)
+}
+
+/// Clears every (orthogonally) connected group of set tiles that has fewer than `min_size` tiles.
+/// Invert the mask first, and back again after, to fill in small holes instead.
+pub fn remove_small_features(mask: &BitGrid, min_size: usize) -> BitGrid {
+    let mut out = mask.clone();
+    let mut seen = Grid::<bool>::new(mask.width(), mask.height());
+    for start in mask.ones() {
+        if seen[start] {
+            continue;
+        }
+        // gather the whole group before deciding whether to keep it
+        let mut group = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        seen[start] = true;
+        while let Some(addr) = queue.pop_front() {
+            group.push(addr);
+            for neighbor in seen.neighbors4(addr).collect::<Vec<TileAddress>>() {
+                if mask.get(&neighbor) && !seen[neighbor] {
+                    seen[neighbor] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        if group.len() < min_size {
+            for addr in group {
+                out.set(&addr, false);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+
+    fn mask(width: u32, height: u32, set: impl Fn(u32, u32) -> bool) -> BitGrid {
+        BitGrid::from_grid(&Grid::from_fn(width, height, |TileAddress(x, y)| set(x, y)), |bit| *bit)
+    }
+
+    fn ones(mask: &BitGrid) -> Vec<(u32, u32)> {
+        mask.ones().map(|addr| addr.as_tuple()).collect()
+    }
+
+    #[test]
+    fn even_squares_reach_further_south_and_west() {
+        let square = Structuring::Square { size: 2 };
+        assert_eq!(square.offsets(), [(-1, -1), (0, -1), (-1, 0), (0, 0)]);
+
+        // stamped around the set tile, so it grows south and west
+        let dot = mask(6, 6, |x, y| (x, y) == (3, 3));
+        assert_eq!(ones(&dilate(&dot, &square)), [(2, 2), (3, 2), (2, 3), (3, 3)]);
+
+        // and only the tile that has the whole shape to its south and west survives eroding
+        let block = mask(6, 6, |x, y| (2..=3).contains(&x) && (2..=3).contains(&y));
+        assert_eq!(ones(&erode(&block, &square)), [(3, 3)]);
+        assert_eq!(open(&block, &square), block);
+
+        // while a line one tile wide doesn't survive opening at all
+        let line = mask(8, 6, |x, y| y == 2 && (2..=5).contains(&x));
+        assert!(open(&line, &square).is_clear());
+        assert_eq!(close(&line, &square), line);
+    }
+
+    #[test]
+    fn opening_and_closing_twice_changes_nothing() {
+        let elements = [
+            Structuring::Square { size: 1 },
+            Structuring::Square { size: 2 },
+            Structuring::Square { size: 3 },
+            Structuring::Cross { radius: 1 },
+            Structuring::Disc { radius: 2 },
+        ];
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..50 {
+            let density = rng.gen_range(0.2..0.8);
+            let (width, height) = (rng.gen_range(3..16), rng.gen_range(3..16));
+            let bits: Vec<bool> = (0..width * height).map(|_| rng.gen_bool(density)).collect();
+            let random = mask(width, height, |x, y| bits[(y * width + x) as usize]);
+            for element in &elements {
+                let opened = open(&random, element);
+                assert_eq!(open(&opened, element), opened, "{element:?}");
+                assert!(opened.ones().all(|addr| random.get(&addr)), "{element:?}");
+
+                let closed = close(&random, element);
+                assert_eq!(close(&closed, element), closed, "{element:?}");
+                assert!(random.ones().all(|addr| closed.get(&addr)), "{element:?}");
+            }
+        }
+    }
+}
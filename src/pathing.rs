@@ -3,12 +3,14 @@ use bevy::input::Input;
 use bevy::prelude::*;
 use bevy_rapier2d::control::KinematicCharacterController;
 use pathfinding::directed::astar;
+use crate::cardinal::{Cardinal, Ordinal};
+use crate::editor::EditorState;
 use crate::fill::Tiles;
 
 use crate::grid::{Grid, TileAddress};
 use crate::input::PlayerCursor;
 use crate::PlayerControlled;
-use crate::zone::{GridDimensions, TileState};
+use crate::zone::{GridDimensions, TileState, ZoneScoped, ZoneSystems, ZoneWillRegenerate};
 
 pub struct PathingPlugin;
 
@@ -26,10 +28,11 @@ struct ComputedPath {
 impl Plugin for PathingPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, update_movement_agents)
-            .add_systems(Update, handle_player_nav)
+            .add_systems(Update, update_movement_agents.run_if(in_state(EditorState::Playing)))
+            .add_systems(Update, handle_player_nav.run_if(in_state(EditorState::Playing)))
             .add_systems(Update, compute_paths)
             .add_systems(Update, show_path_sprites)
+            .add_systems(Update, forget_paths_on_regenerate.in_set(ZoneSystems::Cleanup))
         ;
     }
 }
@@ -98,22 +101,16 @@ fn find_path(grid: &Grid<TileState>, start: TileAddress, goal: TileAddress) -> O
     let (path, _) = astar::astar(
         &start,
         |&tile | {
-            let cardinal_ds = [(0, 1), (1, 0), (0, -1), (-1, 0)]; // NESW
-            let cardinals = cardinal_ds.map(|dv|  (tile + dv).filter(is_floor));
-            let try_diagonal = |i: usize, j: usize, dx: i32, dy: i32| {
-                if cardinals[i].is_some() && cardinals[j].is_some() {
-                    (tile + (dx, dy)).filter(is_floor)
-                } else {
-                    None
-                }
-            };
-            let diagonals = [
-                try_diagonal(0, 1, 1, 1),
-                try_diagonal(1, 2, 1, -1),
-                try_diagonal(2, 3, -1, -1),
-                try_diagonal(3, 0, -1, 1),
-            ];
-            diagonals.into_iter().flatten().map(|t| (t, 1414))
+            let step = |direction: Ordinal| tile.step(direction).filter(is_floor);
+            let cardinals = Cardinal::ALL.map(|cardinal| step(cardinal.into()));
+            // no cutting corners: a diagonal step needs both of the cardinal steps beside it to be open
+            let diagonals: Vec<TileAddress> = Ordinal::diagonals().filter_map(|diagonal| {
+                let (a, b) = diagonal.cardinals().unwrap();
+                tile.step(a).filter(is_floor)?;
+                tile.step(b).filter(is_floor)?;
+                step(diagonal)
+            }).collect();
+            diagonals.into_iter().map(|t| (t, 1414))
                 .chain(cardinals.into_iter().flatten().map(|t| (t, 1000)))
         },
         |tile| {
@@ -127,6 +124,21 @@ fn find_path(grid: &Grid<TileState>, start: TileAddress, goal: TileAddress) -> O
     Some(path)
 }
 
+// paths computed against the old zone are meaningless in the new one
+// (their sprites are `ZoneScoped`, so they get despawned separately)
+fn forget_paths_on_regenerate(
+    mut will_regenerate: EventReader<ZoneWillRegenerate>,
+    agents: Query<Entity, Or<(With<ComputedPath>, With<DestinationGoal>, With<PathSprites>)>>,
+    mut commands: Commands,
+) {
+    if will_regenerate.iter().count() == 0 {
+        return;
+    }
+    for entity in &agents {
+        commands.entity(entity).remove::<(ComputedPath, DestinationGoal, PathSprites)>();
+    }
+}
+
 #[derive(Component)]
 struct PathSprite;
 
@@ -149,7 +161,7 @@ fn show_path_sprites(
             let xy = dims.world_pos_of(tile) + Vec2::splat(dims.tile_size * 0.5);
             commands.spawn(SpriteBundle {
                 sprite: Sprite {
-                    custom_size: Some(Vec2::splat(0.25)),
+                    custom_size: Some(Vec2::splat(dims.tile_size * 0.25)),
                     color: Color::ORANGE,
                     ..default()
                 },
@@ -159,7 +171,7 @@ fn show_path_sprites(
                     ..default()
                 },
                 ..default()
-            }).insert(PathSprite).id()
+            }).insert(PathSprite).insert(ZoneScoped).id()
         }).collect();
 
         commands.entity(entity).remove::<PathSprites>().insert(PathSprites(new_sprites));
@@ -1,40 +1,110 @@
+use std::ops::ControlFlow;
+
 use bevy::prelude::*;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Grid, GridDimensions, Noise, TileAddress};
+use crate::bit_grid::BitGrid;
+use crate::chunked::SignedTileAddress;
+use crate::fill::{Connectivity, query_region};
+use crate::morphology::{Structuring, dilate, open};
+
+/// Parameters for the shapes that get summed together to form an island.
+/// Together with the noise seed, these fully determine the generated grid.
+#[derive(Resource, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IslandConfig {
+    pub bump_count: u32,
+    pub bridge_count: u32,
+    /// Bump radius range, as a fraction of the world width
+    pub bump_radius_min: f32,
+    pub bump_radius_max: f32,
+    /// Bridge thickness, in tiles
+    pub bridge_thickness: f32,
+    /// Bridges are widened to at least this many tiles wherever they made it above water
+    #[serde(default)]
+    pub bridge_min_width: f32,
+    /// Strips land that's only one tile wide, like spits and narrow necks, which the player can't get into
+    #[serde(default)]
+    pub remove_pinches: bool,
+}
+
+impl Default for IslandConfig {
+    fn default() -> Self {
+        IslandConfig {
+            bump_count: 5,
+            bridge_count: 3,
+            bump_radius_min: 0.15,
+            bump_radius_max: 0.30,
+            bridge_thickness: 3.0,
+            bridge_min_width: 3.0,
+            remove_pinches: true,
+        }
+    }
+}
 
-use crate::{flood_fill, Grid, GridDimensions, Noise, TileAddress};
-use crate::fill::Tiles;
+pub fn generate_island_into<T, F>(dims: &GridDimensions, noise: &Noise, config: &IslandConfig, out: &mut Grid<T>, f: F)
+    where F: Fn(Reachability) -> T
+{
+    let _ = generate_island_with_progress(dims, noise, config, out, f, &mut |_| ControlFlow::Continue(()));
+}
 
-pub fn generate_island_into<T, F>(dims: &GridDimensions, noise: &Noise, out: &mut Grid<T>, f: F)
+/// Same as `generate_island_into`, but calls `progress` with the fraction of work done
+/// (from 0.0 to 1.0) as generation goes along. If `progress` returns `Break`, generation
+/// stops there and returns `Break`; `out` is only written once all the work is done.
+pub fn generate_island_with_progress<T, F>(
+    dims: &GridDimensions,
+    noise: &Noise,
+    config: &IslandConfig,
+    out: &mut Grid<T>,
+    f: F,
+    progress: &mut dyn FnMut(f32) -> ControlFlow<()>,
+) -> ControlFlow<()>
     where F: Fn(Reachability) -> T
 {
+    // sampling the noise is the bulk of the work, so it gets most of the progress range
+    const NOISE_PROGRESS: f32 = 0.8;
+
     let mut grid = Grid::<TileGenState>::new_from_dims(dims);
 
-    let shaping_func = SummingGroup::new_random_in(dims);
+    let mut rng = StdRng::seed_from_u64(noise.seed() as u64);
+    let (bumps, bridges) = random_shapes_in(dims, config, &mut rng);
+    let bridge_lines: Vec<(Vec2, Vec2)> = bridges.iter().map(|bridge| (bridge.start, bridge.end)).collect();
+    let shaping_func = SummingGroup::from_shapes(bumps, bridges);
         // SummingGroup::new_demo_in(dims);
 
     // init the grid to a simplex-noise island
-    for addr in grid.addresses() {
-        let reachability = pick_reachability(noise, &shaping_func,dims, &addr);
-        grid[addr] = reachability.into();
+    let height = grid.height();
+    for y in 0..height {
+        for x in 0..grid.width() {
+            let addr = TileAddress(x, y);
+            let reachability = pick_reachability(noise, &shaping_func,dims, &addr);
+            grid[addr] = reachability.into();
+        }
+        progress(NOISE_PROGRESS * (y + 1) as f32 / height as f32)?;
     }
 
+    clean_up_land(&mut grid, dims, config, &bridge_lines);
+
     // in case of multiple separate island areas, find the biggest one and treat it as the "primary"
     let primary_group_id = {
         let mut current_group_id = GroupId::default();
-        let mut biggest_group = (current_group_id, 0u64);
+        let mut biggest_group = (current_group_id, 0usize);
         for addr in grid.addresses() {
-            // when we find an "unassigned" tile, do a flood fill, assigning it and
-            // all connected tiles to the current group, and keeping track of how
-            // many tiles were in the new group to update biggest_group
+            // when we find an "unassigned" tile, find the region of connected tiles, assign
+            // them all to the current group, and update biggest_group with the region's size
             if grid[addr] == TileGenState::Unassigned {
-                let mut grid_proxy = GridProxy { grid: &mut grid, group_size: 0 };
-                flood_fill(
-                    &mut grid_proxy,
+                let region = query_region(
+                    &grid,
                     addr.as_tuple(),
-                    |a, b| { *a == *b },
-                    TileGenState::ReachableGroup(current_group_id),
+                    |tile| *tile == TileGenState::Unassigned,
+                    Connectivity::Four,
+                    None,
                 );
-                let current_group_size = grid_proxy.group_size;
+                for (x, y) in region.addresses() {
+                    grid[TileAddress(x, y)] = TileGenState::ReachableGroup(current_group_id);
+                }
+                let current_group_size = region.size();
 
                 if current_group_size > biggest_group.1 {
                     biggest_group = (current_group_id, current_group_size);
@@ -46,6 +116,7 @@ pub fn generate_island_into<T, F>(dims: &GridDimensions, noise: &Noise, out: &mu
 
         biggest_group.0
     };
+    progress(0.9)?;
 
     for addr in out.addresses() {
         out[addr] = f(match grid[addr] {
@@ -56,25 +127,131 @@ pub fn generate_island_into<T, F>(dims: &GridDimensions, noise: &Noise, out: &mu
             TileGenState::Unassigned => Reachability::Closed,
         });
     }
+    progress(1.0)
 }
 
-struct GridProxy<'a> {
-    grid: &'a mut Grid<TileGenState>,
-    group_size: u64,
+/// The continuous elevation that `generate_island_into` thresholds at `SEA_LEVEL`, sampled at the
+/// same point of each tile. Unlike the generated tiles, this still includes the smaller islands
+/// that generation closes off.
+pub fn sample_island_elevation(dims: &GridDimensions, noise: &Noise, config: &IslandConfig) -> Grid<f32> {
+    let mut rng = StdRng::seed_from_u64(noise.seed() as u64);
+    let shaping_func = SummingGroup::new_random_in(dims, config, &mut rng);
+
+    let mut elevation = Grid::new_from_dims(dims);
+    for addr in elevation.addresses() {
+        let world_pos = dims.world_pos_of(&addr);
+        let e = pick_elevation(noise, dims.normalize_from_center(world_pos));
+        elevation[addr] = blend_elevation(e, shaping_func.compute_at(world_pos));
+    }
+    elevation
 }
 
-impl<'a> Tiles<u32> for GridProxy<'a> {
-    type Tile = TileGenState;
+/// Generates the tiles of an unbounded world, one rectangular window at a time.
+///
+/// Unlike `generate_island_into`, everything is computed in world space: the plane is divided
+/// into square cells, and each cell scatters its own bumps and bridges using an RNG seeded from
+/// the world seed and the cell's coordinates. Windows generated separately (or regenerated after
+/// being unloaded) therefore line up seamlessly and come out identical every time.
+#[derive(Clone)]
+pub struct WorldGenerator {
+    noise: Noise,
+    config: IslandConfig,
+    dims: GridDimensions,
+    cell_size: f32,
+}
 
-    fn get_tile(&self, x: u32, y: u32) -> Option<&Self::Tile> {
-        self.grid.tile_at(&TileAddress(x, y))
+impl WorldGenerator {
+    /// `dims` provides the `tile_size` and the world position of the (0, 0) tile;
+    /// its `size_in_tiles` is ignored. `cell_size` is in world units.
+    pub fn new(seed: u32, config: IslandConfig, dims: GridDimensions, cell_size: f32) -> Self {
+        WorldGenerator {
+            noise: Noise::with_seed(seed),
+            config,
+            dims,
+            cell_size,
+        }
+    }
+
+    pub fn dimensions(&self) -> &GridDimensions {
+        &self.dims
+    }
+
+    /// Fills `out` with the tiles of the window whose bottom-left tile is at `origin`.
+    pub fn generate_into<T, F>(&self, origin: SignedTileAddress, out: &mut Grid<T>, f: F)
+        where F: Fn(Reachability) -> T
+    {
+        let min = self.dims.world_pos_of_signed(&origin);
+        let max = min + Vec2::new(out.width() as f32, out.height() as f32) * self.dims.tile_size;
+        let shaping_func = self.shaping_between(min, max);
+
+        for addr in out.addresses() {
+            let tile = origin + (addr.0 as i32, addr.1 as i32);
+            out[addr] = f(self.pick_reachability(&shaping_func, &tile));
+        }
+    }
+
+    fn pick_reachability(&self, shaping: &dyn ShapingFunction, tile: &SignedTileAddress) -> Reachability {
+        let world_pos = self.dims.world_pos_of_signed(tile);
+        // scale the noise so that a cell sees about as much variation as a whole zone does
+        let e = pick_elevation(&self.noise, world_pos * (2.0 / self.cell_size));
+        combine_elevation(e, shaping.compute_at(world_pos))
     }
 
-    fn set_tile(&mut self, x: u32, y: u32, tile: Self::Tile) {
-        if let Some(out) = self.grid.tile_at_mut(&TileAddress(x, y)) {
-            *out = tile;
-            self.group_size += 1;
+    /// Collects the shaping functions of every cell that can influence the given world-space rectangle.
+    /// Bumps and bridges never reach further than one cell from the cell that spawned them.
+    fn shaping_between(&self, min: Vec2, max: Vec2) -> SummingGroup {
+        let cell_min = (min / self.cell_size).floor().as_ivec2() - IVec2::ONE;
+        let cell_max = (max / self.cell_size).floor().as_ivec2() + IVec2::ONE;
+        let cells = (cell_min.y..=cell_max.y).flat_map(|y| {
+            (cell_min.x..=cell_max.x).map(move |x| IVec2::new(x, y))
+        });
+        SummingGroup(cells.map(|cell| boxed(self.cell_shaping(cell))).collect())
+    }
+
+    fn cell_shaping(&self, cell: IVec2) -> SummingGroup {
+        let mut rng = StdRng::seed_from_u64(cell_seed(self.noise.seed(), cell));
+        let cell_tiles = (self.cell_size / self.dims.tile_size).ceil() as u32;
+        let mut cell_dims = GridDimensions::new([cell_tiles, cell_tiles]);
+        cell_dims.tile_size = self.dims.tile_size;
+        cell_dims.bottom_left = cell.as_vec2() * self.cell_size;
+        SummingGroup::new_random_in(&cell_dims, &self.config, &mut rng)
+    }
+}
+
+// mixes the world seed with a cell's coordinates to get a per-cell seed. The coordinates are packed
+// into separate halves of one word, so for a given world seed, every cell gets a different seed
+fn cell_seed(seed: u32, cell: IVec2) -> u64 {
+    let coords = ((cell.x as u32 as u64) << 32) | cell.y as u32 as u64;
+    splitmix64(splitmix64(seed as u64) ^ coords)
+}
+
+// the splitmix64 finalizer, which scrambles the bits of a word without any collisions
+fn splitmix64(z: u64) -> u64 {
+    let mut z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// tidies up the thresholded noise before it's split into groups
+fn clean_up_land(grid: &mut Grid<TileGenState>, dims: &GridDimensions, config: &IslandConfig, bridges: &[(Vec2, Vec2)]) {
+    let mut land = BitGrid::from_grid(grid, |tile| *tile == TileGenState::Unassigned);
+    if config.remove_pinches {
+        land = open(&land, &Structuring::Square { size: 2 });
+    }
+    if config.bridge_min_width > 1.0 {
+        // the middle of each bridge, where it's above water, grown out to the minimum width
+        let mut spines = BitGrid::new(land.width(), land.height());
+        for &(start, end) in bridges {
+            for addr in dims.tiles_on_segment(start, end) {
+                spines.set(&addr, land.get(&addr));
+            }
         }
+        let radius = ((config.bridge_min_width - 1.0) * 0.5).ceil() as u32;
+        land.union_with(&dilate(&spines, &Structuring::Disc { radius }));
+    }
+    for addr in grid.addresses() {
+        grid[addr] = if land.get(&addr) { TileGenState::Unassigned } else { TileGenState::Unreachable };
     }
 }
 
@@ -115,10 +292,20 @@ fn pick_reachability(noise: &Noise, shaping: &dyn ShapingFunction, dims: &GridDi
     let pos = dims.normalize_from_center(dims.world_pos_of(address));
     let e = pick_elevation(&noise, pos);
     let world_pos = dims.world_pos_of(address);
-    let d = shaping.compute_at(world_pos) * 0.6 + 0.2;
-    let e2 = (e + d) * 0.5;
+    combine_elevation(e, shaping.compute_at(world_pos))
+}
 
-    if e2 > 0.5 { Reachability::Open } else { Reachability::Closed }
+/// Land elevation above this is open, below it is closed
+pub const SEA_LEVEL: f32 = 0.5;
+
+// blends a noise elevation with the value of a shaping function to decide if a tile is land
+fn combine_elevation(e: f32, shaping: f32) -> Reachability {
+    if blend_elevation(e, shaping) > SEA_LEVEL { Reachability::Open } else { Reachability::Closed }
+}
+
+fn blend_elevation(e: f32, shaping: f32) -> f32 {
+    let d = shaping * 0.6 + 0.2;
+    (e + d) * 0.5
 }
 
 // picks an "elevation" in the range (0.0, 1.0) for the given XY coordinate
@@ -143,31 +330,47 @@ trait ShapingFunction {
 struct SummingGroup(Vec<Box<dyn ShapingFunction>>);
 
 impl SummingGroup {
-    fn new_random_in(dims: &GridDimensions) -> Self {
-        let points: Vec<Vec2> =  (0..5).map(|_| {
-            dims.bottom_left + Vec2::new(dims.world_width() * random::<f32>(), dims.world_height() * random::<f32>())
-        }).collect();
-
-        let bumps = points.iter().map(|center| {
-            boxed(CircleBump {
-                center: center.clone(),
-                radius: dims.world_width() * (0.15 + random::<f32>() * 0.15),
-            })
-        });
-
-        let bridges = (0..3).map(|_| {
-            let endpoints: Vec<Vec2> = points.choose_multiple(&mut thread_rng(), 2).cloned().collect();
-            boxed(BridgeBump {
-                start: endpoints[0],
-                end: endpoints[1],
-                thickness: dims.tile_size * 3.0,
-            })
-        });
+    fn new_random_in<R: Rng>(dims: &GridDimensions, config: &IslandConfig, rng: &mut R) -> Self {
+        let (bumps, bridges) = random_shapes_in(dims, config, rng);
+        SummingGroup::from_shapes(bumps, bridges)
+    }
 
+    fn from_shapes(bumps: Vec<CircleBump>, bridges: Vec<BridgeBump>) -> Self {
+        let bumps = bumps.into_iter().map(boxed);
+        let bridges = bridges.into_iter().map(boxed);
         SummingGroup(bumps.chain(bridges).collect())
     }
 }
 
+// scatters bumps around the area, and bridges between pairs of them
+fn random_shapes_in<R: Rng>(dims: &GridDimensions, config: &IslandConfig, rng: &mut R) -> (Vec<CircleBump>, Vec<BridgeBump>) {
+    let points: Vec<Vec2> = (0..config.bump_count).map(|_| {
+        dims.bottom_left + Vec2::new(dims.world_width() * rng.gen::<f32>(), dims.world_height() * rng.gen::<f32>())
+    }).collect();
+
+    let radius_range = config.bump_radius_max - config.bump_radius_min;
+    let bumps: Vec<CircleBump> = points.iter().map(|center| {
+        CircleBump {
+            center: center.clone(),
+            radius: dims.world_width() * (config.bump_radius_min + rng.gen::<f32>() * radius_range),
+        }
+    }).collect();
+
+    let bridges: Vec<BridgeBump> = (0..config.bridge_count).filter_map(|_| {
+        let endpoints: Vec<Vec2> = points.choose_multiple(&mut *rng, 2).cloned().collect();
+        if endpoints.len() < 2 {
+            return None;
+        }
+        Some(BridgeBump {
+            start: endpoints[0],
+            end: endpoints[1],
+            thickness: dims.tile_size * config.bridge_thickness,
+        })
+    }).collect();
+
+    (bumps, bridges)
+}
+
 fn boxed<F: ShapingFunction + 'static>(f: F) -> Box<dyn ShapingFunction> {
     Box::new(f)
 }
@@ -209,4 +412,50 @@ impl ShapingFunction for  BridgeBump {
         if dist_ratio > 1.0 { 0.0 }
         else { (1.0 - dist_ratio).powf(0.5) * 0.75 }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaking_from_progress_stops_generation() {
+        let dims = GridDimensions::new([24, 16]);
+        let (noise, config) = (Noise::with_seed(1), IslandConfig::default());
+
+        let mut fractions = Vec::new();
+        let mut out = Grid::<u8>::new(24, 16);
+        let finished = generate_island_with_progress(&dims, &noise, &config, &mut out, |_| 1, &mut |fraction| {
+            fractions.push(fraction);
+            ControlFlow::Continue(())
+        });
+        assert!(finished.is_continue());
+        assert!(fractions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(fractions.last(), Some(&1.0));
+
+        let mut calls = 0;
+        let mut out = Grid::<u8>::new(24, 16);
+        let finished = generate_island_with_progress(&dims, &noise, &config, &mut out, |_| 1, &mut |_| {
+            calls += 1;
+            if calls == 3 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        });
+        assert!(finished.is_break());
+        assert_eq!(calls, 3);
+        assert!(out.addresses().all(|addr| out[addr] == 0));
+    }
+
+    #[test]
+    fn neighboring_cells_and_seeds_get_different_cell_seeds() {
+        let mut seen = std::collections::HashSet::new();
+        for seed in [0, 1, 2, u32::MAX] {
+            for y in -4..=4 {
+                for x in -4..=4 {
+                    assert!(seen.insert(cell_seed(seed, IVec2::new(x, y))), "seed {seed}, cell ({x}, {y})");
+                }
+            }
+            // far apart along either axis, where the old bit ranges used to overlap
+            for (x, y) in [(1 << 16, 0), (0, 1 << 24), (i32::MIN, 0), (0, i32::MIN), (-1, -1 << 16)] {
+                assert!(seen.insert(cell_seed(seed, IVec2::new(x, y))), "seed {seed}, cell ({x}, {y})");
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Versioned on-disk format for zones.
+//!
+//! Zones are saved as a compact binary file by default: a magic number and format version,
+//! followed by metadata, dimensions, generator settings, and the run-length encoded tiles.
+//! Files with a `.ron` extension use a RON form of the same data, which is easier to diff.
+
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::grid::Grid;
+use crate::procgen::IslandConfig;
+use crate::zone::{GridDimensions, TileState};
+
+const MAGIC: &[u8; 4] = b"GZON";
+
+/// Bump this whenever the layout of either save format changes.
+pub const SAVE_FORMAT_VERSION: u16 = 2;
+
+pub const GENERATOR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ZoneMetadata {
+    pub name: String,
+    pub generator_version: String,
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+}
+
+impl ZoneMetadata {
+    pub fn now(name: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        ZoneMetadata {
+            name,
+            generator_version: GENERATOR_VERSION.to_string(),
+            timestamp,
+        }
+    }
+}
+
+pub struct ZoneSave {
+    pub metadata: ZoneMetadata,
+    pub dimensions: GridDimensions,
+    pub seed: u32,
+    pub config: IslandConfig,
+    pub tiles: Grid<TileState>,
+}
+
+pub fn save_zone<P: AsRef<Path>>(path: P, save: &ZoneSave) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut writer = BufWriter::new(File::create(path)?);
+    let zone = RonZone::from_save(save);
+    if is_ron(path) {
+        let text = ron::ser::to_string_pretty(&zone, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        writer.write_all(text.as_bytes())?;
+    } else {
+        write_binary(&mut writer, &zone)?;
+    }
+    writer.flush()
+}
+
+pub fn load_zone<P: AsRef<Path>>(path: P) -> io::Result<ZoneSave> {
+    let path = path.as_ref();
+    let mut reader = BufReader::new(File::open(path)?);
+    let zone = if is_ron(path) {
+        ron::de::from_reader(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+    } else {
+        read_binary(&mut reader)?
+    };
+    zone.into_save()
+}
+
+fn is_ron(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "ron")
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The serialized form of a `ZoneSave`, shared by both formats.
+#[derive(Serialize, Deserialize)]
+struct RonZone {
+    version: u16,
+    metadata: ZoneMetadata,
+    size_in_tiles: [u32; 2],
+    tile_size: f32,
+    bottom_left: [f32; 2],
+    seed: u32,
+    config: IslandConfig,
+    /// Consecutive tiles in row-major order, as `(count, tile)` pairs
+    runs: Vec<(u32, TileState)>,
+}
+
+impl RonZone {
+    fn from_save(save: &ZoneSave) -> Self {
+        RonZone {
+            version: SAVE_FORMAT_VERSION,
+            metadata: save.metadata.clone(),
+            size_in_tiles: [save.tiles.width(), save.tiles.height()],
+            tile_size: save.dimensions.tile_size,
+            bottom_left: save.dimensions.bottom_left.into(),
+            seed: save.seed,
+            config: save.config,
+            runs: encode_runs(&save.tiles),
+        }
+    }
+
+    fn into_save(self) -> io::Result<ZoneSave> {
+        if self.version > SAVE_FORMAT_VERSION {
+            return Err(invalid_data("zone was saved by a newer version of the format"));
+        }
+        let mut dimensions = GridDimensions::new(self.size_in_tiles);
+        dimensions.tile_size = self.tile_size;
+        dimensions.bottom_left = Vec2::from(self.bottom_left);
+        let tiles = decode_runs(&dimensions, &self.runs)
+            .ok_or_else(|| invalid_data("tile runs do not match the zone size"))?;
+        Ok(ZoneSave {
+            metadata: self.metadata,
+            dimensions,
+            seed: self.seed,
+            config: self.config,
+            tiles,
+        })
+    }
+}
+
+fn encode_runs(grid: &Grid<TileState>) -> Vec<(u32, TileState)> {
+    let mut runs: Vec<(u32, TileState)> = Vec::new();
+    for addr in grid.addresses() {
+        let tile = grid[addr];
+        match runs.last_mut() {
+            Some((count, run_tile)) if *run_tile == tile => *count += 1,
+            _ => runs.push((1, tile)),
+        }
+    }
+    runs
+}
+
+fn decode_runs(dims: &GridDimensions, runs: &[(u32, TileState)]) -> Option<Grid<TileState>> {
+    let mut grid = Grid::new_from_dims(dims);
+    let mut addresses = grid.addresses();
+    let mut total = 0u64;
+    for &(count, tile) in runs {
+        total += count as u64;
+        for addr in addresses.by_ref().take(count as usize) {
+            grid[addr] = tile;
+        }
+    }
+    let expected = dims.size_in_tiles[0] as u64 * dims.size_in_tiles[1] as u64;
+    if total == expected { Some(grid) } else { None }
+}
+
+fn tile_to_byte(tile: TileState) -> u8 {
+    match tile {
+        TileState::Floor => 0,
+        TileState::Water => 1,
+    }
+}
+
+fn tile_from_byte(byte: u8) -> io::Result<TileState> {
+    match byte {
+        0 => Ok(TileState::Floor),
+        1 => Ok(TileState::Water),
+        _ => Err(invalid_data("unknown tile type")),
+    }
+}
+
+fn write_binary<W: Write>(w: &mut W, zone: &RonZone) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&zone.version.to_le_bytes())?;
+
+    write_str(w, &zone.metadata.name)?;
+    write_str(w, &zone.metadata.generator_version)?;
+    w.write_all(&zone.metadata.timestamp.to_le_bytes())?;
+
+    w.write_all(&zone.size_in_tiles[0].to_le_bytes())?;
+    w.write_all(&zone.size_in_tiles[1].to_le_bytes())?;
+    w.write_all(&zone.tile_size.to_le_bytes())?;
+    w.write_all(&zone.bottom_left[0].to_le_bytes())?;
+    w.write_all(&zone.bottom_left[1].to_le_bytes())?;
+
+    w.write_all(&zone.seed.to_le_bytes())?;
+    w.write_all(&zone.config.bump_count.to_le_bytes())?;
+    w.write_all(&zone.config.bridge_count.to_le_bytes())?;
+    w.write_all(&zone.config.bump_radius_min.to_le_bytes())?;
+    w.write_all(&zone.config.bump_radius_max.to_le_bytes())?;
+    w.write_all(&zone.config.bridge_thickness.to_le_bytes())?;
+    w.write_all(&zone.config.bridge_min_width.to_le_bytes())?;
+    w.write_all(&[zone.config.remove_pinches as u8])?;
+
+    w.write_all(&(zone.runs.len() as u32).to_le_bytes())?;
+    for &(count, tile) in &zone.runs {
+        w.write_all(&count.to_le_bytes())?;
+        w.write_all(&[tile_to_byte(tile)])?;
+    }
+    Ok(())
+}
+
+fn read_binary<R: Read>(r: &mut R) -> io::Result<RonZone> {
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a zone save file"));
+    }
+    let version = u16::from_le_bytes(read_array(r)?);
+    if version > SAVE_FORMAT_VERSION {
+        return Err(invalid_data("zone was saved by a newer version of the format"));
+    }
+
+    let metadata = ZoneMetadata {
+        name: read_str(r)?,
+        generator_version: read_str(r)?,
+        timestamp: u64::from_le_bytes(read_array(r)?),
+    };
+
+    let size_in_tiles = [read_u32(r)?, read_u32(r)?];
+    let tile_size = read_f32(r)?;
+    let bottom_left = [read_f32(r)?, read_f32(r)?];
+
+    let seed = read_u32(r)?;
+    let mut config = IslandConfig {
+        bump_count: read_u32(r)?,
+        bridge_count: read_u32(r)?,
+        bump_radius_min: read_f32(r)?,
+        bump_radius_max: read_f32(r)?,
+        bridge_thickness: read_f32(r)?,
+        // version 1 zones were generated without any cleanup
+        bridge_min_width: 0.0,
+        remove_pinches: false,
+    };
+    if version >= 2 {
+        config.bridge_min_width = read_f32(r)?;
+        let [remove_pinches] = read_array(r)?;
+        config.remove_pinches = remove_pinches != 0;
+    }
+
+    let run_count = read_u32(r)?;
+    let mut runs = Vec::with_capacity(run_count.min(1 << 16) as usize);
+    for _ in 0..run_count {
+        let count = read_u32(r)?;
+        let [byte] = read_array(r)?;
+        runs.push((count, tile_from_byte(byte)?));
+    }
+
+    Ok(RonZone {
+        version,
+        metadata,
+        size_in_tiles,
+        tile_size,
+        bottom_left,
+        seed,
+        config,
+        runs,
+    })
+}
+
+fn read_array<R: Read, const N: usize>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_array(r)?))
+}
+
+fn read_f32<R: Read>(r: &mut R) -> io::Result<f32> {
+    Ok(f32::from_le_bytes(read_array(r)?))
+}
+
+fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    w.write_all(&(s.len() as u32).to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_str<R: Read>(r: &mut R) -> io::Result<String> {
+    let len = read_u32(r)?;
+    let mut bytes = Vec::new();
+    r.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("string was not valid utf-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::grid::TileAddress;
+
+    use super::*;
+
+    fn sample() -> ZoneSave {
+        let mut dimensions = GridDimensions::new([5, 3]);
+        dimensions.tile_size = 2.0;
+        dimensions.bottom_left = Vec2::new(-3.0, 4.0);
+        ZoneSave {
+            metadata: ZoneMetadata::now("sample".to_string()),
+            dimensions,
+            seed: 42,
+            config: IslandConfig::default(),
+            tiles: Grid::from_fn(5, 3, |TileAddress(x, y)| if (x + y) % 3 == 0 { TileState::Water } else { TileState::Floor }),
+        }
+    }
+
+    fn assert_same(loaded: &ZoneSave, saved: &ZoneSave) {
+        assert_eq!(loaded.metadata.name, saved.metadata.name);
+        assert_eq!(loaded.metadata.timestamp, saved.metadata.timestamp);
+        assert_eq!(loaded.dimensions, saved.dimensions);
+        assert_eq!(loaded.seed, saved.seed);
+        assert_eq!(loaded.config, saved.config);
+        assert_eq!(loaded.tiles, saved.tiles);
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("zone-save-test-{}-{}", std::process::id(), name))
+    }
+
+    fn error_kind<T>(result: io::Result<T>) -> Option<io::ErrorKind> {
+        result.err().map(|e| e.kind())
+    }
+
+    #[test]
+    fn round_trips_through_both_formats() {
+        let saved = sample();
+        for name in ["zone.bin", "zone.ron"] {
+            let path = temp_path(name);
+            save_zone(&path, &saved).unwrap();
+            let loaded = load_zone(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_same(&loaded.unwrap(), &saved);
+        }
+    }
+
+    #[test]
+    fn version_1_zones_load_without_cleanup() {
+        let saved = sample();
+        let zone = RonZone { version: 1, ..RonZone::from_save(&saved) };
+
+        // version 1 binaries stop after the bridge thickness
+        let mut bytes = Vec::new();
+        write_binary(&mut bytes, &zone).unwrap();
+        let config_end = bytes.len() - 4 - zone.runs.len() * 5;
+        bytes.drain(config_end - 5..config_end);
+
+        // and version 1 RON files don't mention the newer settings at all
+        let ron = ron::ser::to_string(&zone).unwrap()
+            .replace(",bridge_min_width:3.0,remove_pinches:true", "");
+        assert!(!ron.contains("remove_pinches"));
+
+        let binary = read_binary(&mut bytes.as_slice()).unwrap().into_save().unwrap();
+        let ron = ron::de::from_str::<RonZone>(&ron).unwrap().into_save().unwrap();
+        for loaded in [binary, ron] {
+            assert_eq!(loaded.config.bridge_min_width, 0.0);
+            assert!(!loaded.config.remove_pinches);
+            assert_eq!(loaded.config.bump_count, saved.config.bump_count);
+            assert_eq!(loaded.tiles, saved.tiles);
+        }
+    }
+
+    #[test]
+    fn rejects_other_files_and_newer_versions() {
+        let zone = RonZone::from_save(&sample());
+        let mut bytes = Vec::new();
+        write_binary(&mut bytes, &zone).unwrap();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[..4].copy_from_slice(b"PNG\0");
+        assert_eq!(error_kind(read_binary(&mut bad_magic.as_slice())), Some(io::ErrorKind::InvalidData));
+
+        let mut future = bytes.clone();
+        future[4..6].copy_from_slice(&(SAVE_FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(error_kind(read_binary(&mut future.as_slice())), Some(io::ErrorKind::InvalidData));
+
+        let future = RonZone { version: SAVE_FORMAT_VERSION + 1, ..zone };
+        assert_eq!(error_kind(future.into_save()), Some(io::ErrorKind::InvalidData));
+    }
+}
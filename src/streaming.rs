@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy_rapier2d::prelude::*;
+use futures_lite::future;
+use rand::random;
+
+use crate::{MainCamera, PlayerControlled};
+use crate::border::{Border, BorderKind};
+use crate::cardinal::Cardinal;
+use crate::chunked::{ChunkAddress, ChunkedGrid, SignedTileAddress};
+use crate::grid::{Grid, TileAddress};
+use crate::procgen::{IslandConfig, WorldGenerator};
+use crate::zone::{GridDimensions, TileState};
+
+/// Generates an unbounded world in chunks around the player (or the main camera, if there is
+/// no player), and unloads chunks that fall too far behind. This is an alternative to the
+/// fixed-size `ZonePlugin`, not something to run alongside it.
+///
+/// Also provides the `GridDimensions` of the chunk at the origin, for the tile size and
+/// the camera's starting view, and pans the main camera with the arrow keys.
+pub struct WorldStreamingPlugin {
+    pub chunk_size: u32,
+    /// Chunks within this many chunks of the focus (in either axis) get generated
+    pub load_radius: i32,
+    /// Chunks further than this many chunks from the focus get unloaded
+    pub unload_radius: i32,
+}
+
+impl Default for WorldStreamingPlugin {
+    fn default() -> Self {
+        WorldStreamingPlugin {
+            chunk_size: 32,
+            load_radius: 2,
+            unload_radius: 3,
+        }
+    }
+}
+
+impl Plugin for WorldStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        let dims = GridDimensions::new([self.chunk_size, self.chunk_size]);
+        let cell_size = self.chunk_size as f32 * dims.tile_size;
+        let generator = WorldGenerator::new(random(), IslandConfig::default(), dims, cell_size);
+        app
+            .insert_resource(dims)
+            .insert_resource(StreamedWorld {
+                tiles: ChunkedGrid::new(self.chunk_size),
+                generator,
+                load_radius: self.load_radius,
+                unload_radius: self.unload_radius.max(self.load_radius),
+                pending: HashMap::new(),
+                chunk_entities: HashMap::new(),
+            })
+            .add_systems(Update, (
+                request_nearby_chunks,
+                spawn_generated_chunks,
+                unload_distant_chunks,
+            ).chain())
+            .add_systems(Update, pan_camera_on_keypress.before(request_nearby_chunks))
+        ;
+    }
+}
+
+#[derive(Resource)]
+pub struct StreamedWorld {
+    pub tiles: ChunkedGrid<TileState>,
+    generator: WorldGenerator,
+    load_radius: i32,
+    unload_radius: i32,
+    pending: HashMap<ChunkAddress, Task<GeneratedChunk>>,
+    chunk_entities: HashMap<ChunkAddress, Vec<Entity>>,
+}
+
+impl StreamedWorld {
+    pub fn dimensions(&self) -> &GridDimensions {
+        self.generator.dimensions()
+    }
+
+    pub fn chunk_at(&self, pos: Vec2) -> ChunkAddress {
+        let tile = self.dimensions().position_to_signed_address(pos);
+        self.tiles.locate(&tile).0
+    }
+
+    // dimensions of a single chunk, positioned where that chunk sits in the world
+    fn chunk_dimensions(&self, chunk: &ChunkAddress) -> GridDimensions {
+        let size = self.tiles.chunk_size();
+        let mut dims = *self.dimensions();
+        dims.size_in_tiles = [size, size];
+        dims.bottom_left = dims.world_pos_of_signed(&self.tiles.chunk_origin(chunk));
+        dims
+    }
+}
+
+/// Marks entities that belong to a streamed chunk, and get despawned when it unloads.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct ChunkMember(pub ChunkAddress);
+
+#[derive(Component)]
+struct StreamedWall;
+
+struct GeneratedChunk {
+    chunk: ChunkAddress,
+    tiles: Grid<TileState>,
+    /// Borders in the chunk's local tile coordinates
+    borders: Vec<Border>,
+}
+
+fn generate_chunk(generator: &WorldGenerator, chunk: ChunkAddress, origin: SignedTileAddress, size: u32) -> GeneratedChunk {
+    // generate a one-tile apron around the chunk, so borders along the chunk's edges
+    // can be decided without waiting for the neighboring chunks to load
+    let mut apron = Grid::new(size + 2, size + 2);
+    generator.generate_into(origin + (-1, -1), &mut apron, TileState::from);
+
+    let mut tiles = Grid::new(size, size);
+    let mut borders = Vec::new();
+    for addr in tiles.addresses() {
+        let apron_addr = TileAddress(addr.0 + 1, addr.1 + 1);
+        let tile = apron[apron_addr];
+        tiles[addr] = tile;
+
+        // each border is emitted by the floor tile on its inside, so neighboring
+        // chunks never both emit the border between them
+        if tile.is_floor() {
+            for cardinal in Cardinal::iter() {
+                let neighbor = apron_addr.step(cardinal).and_then(|n| apron.tile_at(&n));
+                if !neighbor.is_some_and(TileState::is_floor) {
+                    borders.push(Border::at(addr, cardinal, BorderKind::Coast));
+                }
+            }
+        }
+    }
+
+    GeneratedChunk {
+        chunk,
+        tiles,
+        borders,
+    }
+}
+
+fn request_nearby_chunks(
+    mut world: ResMut<StreamedWorld>,
+    players: Query<&GlobalTransform, With<PlayerControlled>>,
+    cameras: Query<&GlobalTransform, With<MainCamera>>,
+) {
+    let focus = players.iter().next().or_else(|| cameras.iter().next());
+    let Some(focus) = focus else { return };
+    let center = world.chunk_at(focus.translation().truncate());
+
+    let radius = world.load_radius;
+    let task_pool = AsyncComputeTaskPool::get();
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let chunk = ChunkAddress(center.0 + dx, center.1 + dy);
+            if world.tiles.is_loaded(&chunk) || world.pending.contains_key(&chunk) {
+                continue;
+            }
+
+            let generator = world.generator.clone();
+            let origin = world.tiles.chunk_origin(&chunk);
+            let size = world.tiles.chunk_size();
+            let task = task_pool.spawn(async move {
+                generate_chunk(&generator, chunk, origin, size)
+            });
+            world.pending.insert(chunk, task);
+        }
+    }
+}
+
+fn spawn_generated_chunks(
+    mut world: ResMut<StreamedWorld>,
+    mut commands: Commands,
+) {
+    let mut finished = Vec::new();
+    for task in world.pending.values_mut() {
+        if let Some(generated) = future::block_on(future::poll_once(task)) {
+            finished.push(generated);
+        }
+    }
+
+    for generated in finished {
+        let chunk = generated.chunk;
+        world.pending.remove(&chunk);
+
+        let dims = world.chunk_dimensions(&chunk);
+        let origin = world.tiles.chunk_origin(&chunk);
+        let mut entities = Vec::new();
+
+        for addr in generated.tiles.addresses() {
+            let tile_state = generated.tiles[addr];
+            let entity = commands
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::splat(dims.tile_size)),
+                        anchor: Anchor::BottomLeft,
+                        color: tile_state.as_color(),
+                        ..default()
+                    },
+                    transform: Transform::from_translation((dims.world_pos_of(&addr), 0.).into()),
+                    ..default()
+                })
+                .insert(origin + (addr.0 as i32, addr.1 as i32))
+                .insert(tile_state)
+                .insert(ChunkMember(chunk))
+                .id();
+            entities.push(entity);
+        }
+
+        for border in &generated.borders {
+            let aabb = border.get_aabb(&dims, 0.1);
+            let center = aabb.center();
+            let size: [f32; 2] = aabb.extents().into();
+            let entity = commands
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        anchor: Anchor::Center,
+                        color: Color::CYAN,
+                        custom_size: Some(size.into()),
+                        ..default()
+                    },
+                    transform: Transform::from_translation((center.x, center.y, 0.).into()),
+                    ..default()
+                })
+                .insert(StreamedWall)
+                .insert(ChunkMember(chunk))
+                .insert(RigidBody::Fixed)
+                .insert(Collider::cuboid(size[0] * 0.5, size[1] * 0.5))
+                .id();
+            entities.push(entity);
+        }
+
+        world.tiles.insert_chunk(chunk, generated.tiles);
+        world.chunk_entities.insert(chunk, entities);
+    }
+}
+
+fn unload_distant_chunks(
+    mut world: ResMut<StreamedWorld>,
+    players: Query<&GlobalTransform, With<PlayerControlled>>,
+    cameras: Query<&GlobalTransform, With<MainCamera>>,
+    mut commands: Commands,
+) {
+    let focus = players.iter().next().or_else(|| cameras.iter().next());
+    let Some(focus) = focus else { return };
+    let center = world.chunk_at(focus.translation().truncate());
+    let radius = world.unload_radius;
+    let is_distant = |chunk: &ChunkAddress| {
+        (chunk.0 - center.0).abs() > radius || (chunk.1 - center.1).abs() > radius
+    };
+
+    // dropping a task cancels it
+    world.pending.retain(|chunk, _| !is_distant(chunk));
+
+    let distant: Vec<ChunkAddress> = world.tiles
+        .loaded_chunks()
+        .map(|(chunk, _)| *chunk)
+        .filter(is_distant)
+        .collect();
+    for chunk in distant {
+        world.tiles.unload_chunk(&chunk);
+        for entity in world.chunk_entities.remove(&chunk).unwrap_or_default() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn pan_camera_on_keypress(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    world: Res<StreamedWorld>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    // tiles per second
+    const SPEED: f32 = 20.;
+    let direction = [
+        (KeyCode::Left, Vec2::NEG_X),
+        (KeyCode::Right, Vec2::X),
+        (KeyCode::Down, Vec2::NEG_Y),
+        (KeyCode::Up, Vec2::Y),
+    ]
+        .into_iter()
+        .filter(|(key, _)| keys.pressed(*key))
+        .map(|(_, direction)| direction)
+        .sum::<Vec2>();
+    let step = direction * SPEED * world.dimensions().tile_size * time.delta_seconds();
+    for mut transform in &mut cameras {
+        transform.translation += step.extend(0.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighboring_chunks_agree_along_their_seam() {
+        let size = 16;
+        let generator = WorldGenerator::new(3, IslandConfig::default(), GridDimensions::new([size, size]), size as f32);
+        let chunks = ChunkedGrid::<TileState>::new(size);
+        let generate = |chunk| generate_chunk(&generator, chunk, chunks.chunk_origin(&chunk), size);
+        let (west, east) = (generate(ChunkAddress(0, 0)), generate(ChunkAddress(1, 0)));
+
+        // generated separately, the chunks match one window spanning both
+        let mut both = Grid::new(size * 2, size);
+        generator.generate_into(chunks.chunk_origin(&ChunkAddress(0, 0)), &mut both, TileState::from);
+        for addr in both.addresses() {
+            let expected = both[addr];
+            if addr.0 < size {
+                assert_eq!(west.tiles[addr], expected);
+            } else {
+                assert_eq!(east.tiles[TileAddress(addr.0 - size, addr.1)], expected);
+            }
+        }
+
+        // so each chunk's apron saw what its neighbor generated, and exactly one side emits each seam border
+        let mut seam_borders = 0;
+        for y in 0..size {
+            let (west_edge, east_edge) = (TileAddress(size - 1, y), TileAddress(0, y));
+            let (west_floor, east_floor) = (west.tiles[west_edge].is_floor(), east.tiles[east_edge].is_floor());
+            let west_border = west.borders.contains(&Border::at(west_edge, Cardinal::East, BorderKind::Coast));
+            let east_border = east.borders.contains(&Border::at(east_edge, Cardinal::West, BorderKind::Coast));
+            assert_eq!(west_border, west_floor && !east_floor);
+            assert_eq!(east_border, east_floor && !west_floor);
+            seam_borders += (west_border || east_border) as u32;
+        }
+        assert!(seam_borders > 0, "the seam should cross the coast somewhere");
+    }
+}
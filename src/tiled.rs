@@ -0,0 +1,476 @@
+//! Export and import of zones in the JSON flavor of the Tiled map format (`.tmj`).
+//! See https://doc.mapeditor.org/en/stable/reference/json-map-format/
+//!
+//! Tiled maps are measured in pixels with Y pointing down, while our grids count rows
+//! upward from `GridDimensions::bottom_left`. The world-space `tile_size` and origin
+//! are stored as custom map properties so that a round-trip keeps the same dimensions.
+
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use bevy::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::border::{Border, collect_borders};
+use crate::grid::{Grid, TileAddress};
+use crate::zone::GridDimensions;
+
+/// Size of a tile in Tiled's pixel space. Tiled requires an integer size,
+/// so the actual world-space `tile_size` is kept in a map property instead.
+const TILE_PIXELS: u32 = 16;
+
+/// Tiled reserves the top bits of each gid for flip flags.
+const GID_FLAGS_MASK: u32 = 0xE000_0000;
+
+const TILE_LAYER_NAME: &str = "tiles";
+const BORDER_LAYER_NAME: &str = "borders";
+const POI_LAYER_NAME: &str = "points_of_interest";
+
+/// Two-way mapping between tile values and Tiled's global tile ids (gids).
+/// Gid `0` means "no tile" in Tiled and is imported as the `fallback` tile;
+/// any other gid that isn't in the mapping is rejected on import.
+pub struct GidMapping<T> {
+    entries: Vec<(u32, T)>,
+    fallback: T,
+}
+
+impl<T: Clone + PartialEq> GidMapping<T> {
+    pub fn new(fallback: T) -> Self {
+        GidMapping {
+            entries: Vec::new(),
+            fallback,
+        }
+    }
+
+    pub fn with(mut self, gid: u32, tile: T) -> Self {
+        self.entries.push((gid, tile));
+        self
+    }
+
+    pub fn gid_of(&self, tile: &T) -> u32 {
+        self.entries
+            .iter()
+            .find(|(_, t)| t == tile)
+            .map_or(0, |(gid, _)| *gid)
+    }
+
+    pub fn tile_of(&self, gid: u32) -> Option<T> {
+        let gid = gid & !GID_FLAGS_MASK;
+        if gid == 0 {
+            return Some(self.fallback.clone());
+        }
+        self.entries
+            .iter()
+            .find(|(g, _)| *g == gid)
+            .map(|(_, t)| t.clone())
+    }
+
+    fn tile_count(&self) -> u32 {
+        self.entries.iter().map(|(gid, _)| *gid).max().unwrap_or(0)
+    }
+}
+
+/// A named location in the zone, exported as a point object.
+#[derive(Clone, Debug)]
+pub struct PointOfInterest {
+    pub name: String,
+    pub pos: Vec2,
+}
+
+/// The result of importing a Tiled map.
+pub struct TiledZone<T> {
+    pub grid: Grid<T>,
+    pub dimensions: GridDimensions,
+    pub points_of_interest: Vec<PointOfInterest>,
+}
+
+pub fn export_zone<T, F, P>(
+    path: P,
+    grid: &Grid<T>,
+    dims: &GridDimensions,
+    mapping: &GidMapping<T>,
+    test_inside: &F,
+    points_of_interest: &[PointOfInterest],
+) -> io::Result<()>
+    where T: Clone + PartialEq,
+          F: Fn(&T) -> bool,
+          P: AsRef<Path>,
+{
+    let map = zone_to_map(grid, dims, mapping, test_inside, points_of_interest);
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &map)?;
+    Ok(())
+}
+
+pub fn import_zone<T, P>(path: P, mapping: &GidMapping<T>) -> io::Result<TiledZone<T>>
+    where T: Default + Clone + PartialEq,
+          P: AsRef<Path>,
+{
+    let reader = BufReader::new(File::open(path)?);
+    let map: TiledMap = serde_json::from_reader(reader)?;
+    map_to_zone(&map, mapping)
+}
+
+fn zone_to_map<T, F>(
+    grid: &Grid<T>,
+    dims: &GridDimensions,
+    mapping: &GidMapping<T>,
+    test_inside: &F,
+    points_of_interest: &[PointOfInterest],
+) -> TiledMap
+    where T: Clone + PartialEq,
+          F: Fn(&T) -> bool,
+{
+    let width = grid.width();
+    let height = grid.height();
+    let to_pixels = PixelSpace { dims: *dims, height };
+
+    // Tiled stores rows top-down
+    let data = (0..height).rev()
+        .flat_map(|y| (0..width).map(move |x| TileAddress(x, y)))
+        .map(|addr| mapping.gid_of(&grid[addr]))
+        .collect();
+
+    let mut next_object_id = 1;
+    let mut new_object = |name: String, class: &str, [x, y]: [f32; 2], [w, h]: [f32; 2], point: bool| {
+        let id = next_object_id;
+        next_object_id += 1;
+        TiledObject {
+            id,
+            name,
+            class: class.to_string(),
+            x,
+            y,
+            width: w,
+            height: h,
+            rotation: 0.,
+            visible: true,
+            point,
+        }
+    };
+
+    let mut border_objects = Vec::new();
+    collect_borders(grid, test_inside, &mut |border: Border| {
+        let aabb = border.get_aabb(dims, 0.1);
+        let top_left = to_pixels.point(Vec2::new(aabb.mins.x, aabb.maxs.y));
+        let size = to_pixels.size(Vec2::new(aabb.maxs.x - aabb.mins.x, aabb.maxs.y - aabb.mins.y));
+        border_objects.push(new_object(String::new(), "border", top_left, size, false));
+    });
+
+    let poi_objects = points_of_interest.iter()
+        .map(|poi| new_object(poi.name.clone(), "poi", to_pixels.point(poi.pos), [0., 0.], true))
+        .collect();
+
+    TiledMap {
+        kind: "map".to_string(),
+        version: "1.10".to_string(),
+        orientation: "orthogonal".to_string(),
+        renderorder: "right-down".to_string(),
+        infinite: false,
+        width,
+        height,
+        tilewidth: TILE_PIXELS,
+        tileheight: TILE_PIXELS,
+        nextlayerid: 4,
+        nextobjectid: next_object_id,
+        properties: vec![
+            TiledProperty::float("tile_size", dims.tile_size),
+            TiledProperty::float("bottom_left_x", dims.bottom_left.x),
+            TiledProperty::float("bottom_left_y", dims.bottom_left.y),
+        ],
+        layers: vec![
+            TiledLayer::TileLayer {
+                id: 1,
+                name: TILE_LAYER_NAME.to_string(),
+                width,
+                height,
+                data: Some(data),
+                encoding: None,
+            },
+            TiledLayer::ObjectGroup {
+                id: 2,
+                name: BORDER_LAYER_NAME.to_string(),
+                objects: border_objects,
+            },
+            TiledLayer::ObjectGroup {
+                id: 3,
+                name: POI_LAYER_NAME.to_string(),
+                objects: poi_objects,
+            },
+        ],
+        tilesets: vec![
+            TiledTileset {
+                firstgid: 1,
+                name: "tiles".to_string(),
+                tilewidth: TILE_PIXELS,
+                tileheight: TILE_PIXELS,
+                tilecount: mapping.tile_count(),
+                columns: mapping.tile_count(),
+            },
+        ],
+    }
+}
+
+fn map_to_zone<T>(map: &TiledMap, mapping: &GidMapping<T>) -> io::Result<TiledZone<T>>
+    where T: Default + Clone + PartialEq,
+{
+    let mut dims = GridDimensions::new([map.width, map.height]);
+    if let Some(tile_size) = map.float_property("tile_size") {
+        dims.tile_size = tile_size;
+    }
+    if let Some(x) = map.float_property("bottom_left_x") {
+        dims.bottom_left.x = x;
+    }
+    if let Some(y) = map.float_property("bottom_left_y") {
+        dims.bottom_left.y = y;
+    }
+
+    let data = map.layers.iter().find_map(|layer| match layer {
+        TiledLayer::TileLayer { width, height, data: Some(data), encoding: None, .. }
+            if *width == map.width && *height == map.height => Some(data),
+        _ => None,
+    }).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "map has no uncompressed tile layer of the expected size")
+    })?;
+    let expected_len = map.width as usize * map.height as usize;
+    if data.len() != expected_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("tile layer has {} tiles, expected {}", data.len(), expected_len),
+        ));
+    }
+
+    let mut grid = Grid::new(map.width, map.height);
+    let rows_top_down = (0..map.height).rev().flat_map(|y| (0..map.width).map(move |x| TileAddress(x, y)));
+    for (addr, gid) in rows_top_down.zip(data.iter()) {
+        grid[addr] = mapping.tile_of(*gid).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("unknown gid {gid} at {addr:?}"))
+        })?;
+    }
+
+    let to_pixels = PixelSpace { dims, height: map.height };
+    let points_of_interest = map.layers.iter()
+        .filter_map(|layer| match layer {
+            TiledLayer::ObjectGroup { objects, .. } => Some(objects),
+            _ => None,
+        })
+        .flatten()
+        .filter(|obj| obj.point)
+        .map(|obj| PointOfInterest {
+            name: obj.name.clone(),
+            pos: to_pixels.world(obj.x, obj.y),
+        })
+        .collect();
+
+    Ok(TiledZone {
+        grid,
+        dimensions: dims,
+        points_of_interest,
+    })
+}
+
+/// Conversions between world space and Tiled's Y-down pixel space.
+struct PixelSpace {
+    dims: GridDimensions,
+    height: u32,
+}
+
+impl PixelSpace {
+    fn scale(&self) -> f32 {
+        TILE_PIXELS as f32 / self.dims.tile_size
+    }
+    fn point(&self, world_pos: Vec2) -> [f32; 2] {
+        let rel = (world_pos - self.dims.bottom_left) * self.scale();
+        [rel.x, (self.height * TILE_PIXELS) as f32 - rel.y]
+    }
+    fn size(&self, world_size: Vec2) -> [f32; 2] {
+        (world_size * self.scale()).into()
+    }
+    fn world(&self, x: f32, y: f32) -> Vec2 {
+        let rel = Vec2::new(x, (self.height * TILE_PIXELS) as f32 - y) / self.scale();
+        self.dims.bottom_left + rel
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TiledMap {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    version: String,
+    orientation: String,
+    #[serde(default)]
+    renderorder: String,
+    #[serde(default)]
+    infinite: bool,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    nextlayerid: u32,
+    #[serde(default)]
+    nextobjectid: u32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+    layers: Vec<TiledLayer>,
+    #[serde(default)]
+    tilesets: Vec<TiledTileset>,
+}
+
+impl TiledMap {
+    fn float_property(&self, name: &str) -> Option<f32> {
+        self.properties.iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.value.as_f64())
+            .map(|v| v as f32)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum TiledLayer {
+    #[serde(rename = "tilelayer")]
+    TileLayer {
+        id: u32,
+        name: String,
+        width: u32,
+        height: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<Vec<u32>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encoding: Option<String>,
+    },
+    #[serde(rename = "objectgroup")]
+    ObjectGroup {
+        id: u32,
+        name: String,
+        objects: Vec<TiledObject>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TiledObject {
+    id: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default)]
+    visible: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    point: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    tilecount: u32,
+    #[serde(default)]
+    columns: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TiledProperty {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    value: serde_json::Value,
+}
+
+impl TiledProperty {
+    fn float(name: &str, value: f32) -> Self {
+        TiledProperty {
+            name: name.to_string(),
+            kind: "float".to_string(),
+            value: value.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn mapping() -> GidMapping<u8> {
+        GidMapping::new(0).with(1, 1).with(2, 2).with(3, 3)
+    }
+
+    fn sample() -> (Grid<u8>, GridDimensions) {
+        let grid = Grid::from_fn(6, 4, |TileAddress(x, y)| ((x + 2 * y) % 4) as u8);
+        let mut dims = GridDimensions::new([6, 4]);
+        dims.tile_size = 1.5;
+        dims.bottom_left = Vec2::new(-7.0, 2.5);
+        (grid, dims)
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tiled-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn round_trips_tiles_and_dimensions() {
+        let (grid, dims) = sample();
+        let pois = [PointOfInterest { name: "spawn".to_string(), pos: Vec2::new(-4.0, 5.5) }];
+        let path = temp_path("round_trip.tmj");
+        export_zone(&path, &grid, &dims, &mapping(), &|t: &u8| *t != 0, &pois).unwrap();
+        let imported = import_zone(&path, &mapping());
+        std::fs::remove_file(&path).unwrap();
+
+        let imported = imported.unwrap();
+        assert_eq!(imported.grid, grid);
+        assert_eq!(imported.dimensions, dims);
+        assert_eq!(imported.points_of_interest.len(), 1);
+        assert_eq!(imported.points_of_interest[0].name, "spawn");
+        assert!(imported.points_of_interest[0].pos.abs_diff_eq(pois[0].pos, 1e-4));
+    }
+
+    #[test]
+    fn rejects_unknown_gids() {
+        let (grid, dims) = sample();
+        let path = temp_path("unknown_gid.tmj");
+        export_zone(&path, &grid, &dims, &mapping(), &|t: &u8| *t != 0, &[]).unwrap();
+        let imported = import_zone(&path, &GidMapping::new(0).with(1, 1).with(2, 2));
+        std::fs::remove_file(&path).unwrap();
+
+        let error = imported.err().expect("gid 3 isn't in the mapping");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("unknown gid 3"));
+    }
+
+    #[test]
+    fn rejects_tile_layers_of_the_wrong_length() {
+        let (grid, dims) = sample();
+        for change in [-1, 1] {
+            let mut map = zone_to_map(&grid, &dims, &mapping(), &|t: &u8| *t != 0, &[]);
+            for layer in &mut map.layers {
+                if let TiledLayer::TileLayer { data: Some(data), .. } = layer {
+                    if change < 0 { data.pop(); } else { data.push(1); }
+                }
+            }
+            let error = map_to_zone(&map, &mapping()).err().expect("the layer doesn't fit the map");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -1,10 +1,27 @@
+use std::io;
 use std::ops::DerefMut;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use serde::{Deserialize, Serialize};
 
+use crate::chunked::SignedTileAddress;
+use crate::distance::distance_transform;
+use crate::fill::{FillOptions, flood_fill_with};
 use crate::grid::*;
+use crate::grid_image;
+use crate::grid_image::Palette;
+use crate::history::{EditHistory, EditRecorder, TileChange, TileEdit};
 use crate::noise::Noise;
 use crate::procgen::*;
+use crate::save::{load_zone, save_zone, ZoneMetadata, ZoneSave};
+use crate::tiled;
+use crate::tiled::GidMapping;
 
 pub struct ZonePlugin(pub u32, pub u32);
 
@@ -12,48 +29,526 @@ impl Plugin for ZonePlugin {
     fn build(&self, app: &mut App) {
         app
             .insert_resource(ZoneNoise(Noise::new()))
+            .insert_resource(IslandConfig::default())
             .insert_resource(Grid::<TileState>::new(self.0, self.1))
             .insert_resource(GridDimensions::new([self.0, self.1]))
+            .insert_resource(ZoneGeneration::default())
+            .insert_resource(StagedZone::default())
+            .insert_resource(EditHistory::<TileState>::default())
+            .insert_resource(TileJournal::default())
             .add_event::<ZoneCommand>()
+            .add_event::<ZoneGenerationProgress>()
+            .add_event::<ZoneGenerated>()
+            .add_event::<ZoneWillRegenerate>()
+            .add_event::<ZoneRegenerated>()
+            .add_event::<TileChanged>()
+            .configure_sets(Update, (
+                ZoneSystems::Commands,
+                ZoneSystems::Cleanup,
+                ZoneSystems::Apply,
+                ZoneSystems::Settle,
+            ).chain())
             .add_systems(Startup, startup_init_zone)
-            .add_systems(Update, handle_zone_commands)
+            .add_systems(Update, (handle_zone_commands, finish_zone_generation).chain().in_set(ZoneSystems::Commands))
+            .add_systems(Update, despawn_zone_scoped.in_set(ZoneSystems::Cleanup))
+            .add_systems(First, clear_tile_journal)
+            .add_systems(Update, (apply_staged_zone, publish_tile_changes).chain().in_set(ZoneSystems::Apply))
+            // a loaded zone is inserted through Commands, which need to land before `Settle`
+            .add_systems(Update, apply_deferred.after(ZoneSystems::Apply).before(ZoneSystems::Settle))
         ;
     }
 }
 
 #[derive(Resource)]
-struct ZoneNoise(Noise);
+pub struct ZoneNoise(Noise);
+
+impl ZoneNoise {
+    /// The noise the current zone was generated with
+    pub fn noise(&self) -> &Noise {
+        &self.0
+    }
+}
 
 #[derive(Event)]
 pub enum ZoneCommand {
     Regenerate,
+    /// Save the current zone. Paths ending in `.ron` are saved as RON, anything else as binary.
+    Save(PathBuf),
+    /// Replace the current zone with one loaded from disk. Besides zone saves,
+    /// this accepts `.png` images and `.tmj` Tiled maps.
+    Load(PathBuf),
+    /// Regenerate the zone with a new size (in tiles), tile size, and world-space origin.
+    Reconfigure {
+        size: [u32; 2],
+        tile_size: f32,
+        bottom_left: Vec2,
+    },
+    SetTile(TileAddress, TileState),
+    SetTiles(Vec<TileAddress>, TileState),
+    /// Set every tile in the rectangle between two corners (inclusive)
+    FillRect {
+        min: TileAddress,
+        max: TileAddress,
+        tile: TileState,
+    },
+    /// Flood fill the region of same-typed tiles containing the given tile
+    FloodFillAt(TileAddress, TileState),
+    /// Copy a grid into the zone with its bottom-left tile at `offset`; out-of-bounds tiles are dropped
+    PasteGrid {
+        grid: Grid<TileState>,
+        offset: TileAddress,
+    },
+    /// Start merging subsequent edits into a single undo step, until `EndEditGroup`
+    BeginEditGroup,
+    EndEditGroup,
+    /// Revert the most recent edit
+    Undo,
+    /// Re-apply the most recently undone edit
+    Redo,
+}
+
+/// Stages of replacing the current zone with a new one, in the order they run.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ZoneSystems {
+    /// Handles `ZoneCommand`s and polls in-flight generation; sends `ZoneWillRegenerate`
+    Commands,
+    /// Reacts to `ZoneWillRegenerate`, while the old zone is still in place
+    Cleanup,
+    /// Swaps in the new zone; sends `ZoneRegenerated`
+    Apply,
+    /// Reacts to `ZoneRegenerated` and `TileChanged`, with the new zone in place
+    /// (including a zone or dimensions inserted through Commands during `Apply`)
+    Settle,
+}
+
+/// Sent when a new zone (generated or loaded) is about to replace the current one.
+/// Handle it in `ZoneSystems::Cleanup` to see the old zone one last time.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct ZoneWillRegenerate;
+
+/// Sent once a new zone (generated or loaded) has replaced the old one.
+/// Handle it in `ZoneSystems::Settle` to see the new zone.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct ZoneRegenerated;
+
+/// Sent for every tile of the `Grid<TileState>` that changed, during `ZoneSystems::Apply`.
+/// Not sent when the whole grid gets replaced by one with different dimensions
+/// (i.e. when it counts as newly added); see `TileJournal::zone_replaced`.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct TileChanged {
+    pub address: TileAddress,
+    pub before: TileState,
+    pub after: TileState,
+}
+
+/// This frame's changes to the `Grid<TileState>` resource, so that downstream systems can
+/// update only what changed. Changes made through `ZoneCommand`s (and regenerations) are
+/// recorded here; anything that mutates the grid directly should record its changes too, or
+/// the systems following the grid have to fall back to rebuilding everything from it.
+#[derive(Resource, Default)]
+pub struct TileJournal {
+    changes: Vec<TileChanged>,
+    published: usize,
+    zone_replaced: bool,
+}
+
+impl TileJournal {
+    pub fn record(&mut self, address: TileAddress, before: TileState, after: TileState) {
+        if before != after {
+            self.changes.push(TileChanged { address, before, after });
+        }
+    }
+
+    pub fn record_edit(&mut self, edit: &TileEdit<TileState>) {
+        for &TileChange { address, before, after } in edit.changes() {
+            self.record(address, before, after);
+        }
+    }
+
+    pub fn record_undo(&mut self, edit: &TileEdit<TileState>) {
+        for &TileChange { address, before, after } in edit.changes().iter().rev() {
+            self.record(address, after, before);
+        }
+    }
+
+    /// Tiles changed so far this frame, in the order they changed. A tile may appear more than once.
+    pub fn changed_tiles(&self) -> impl Iterator<Item=&TileChanged> {
+        self.changes.iter()
+    }
+
+    /// Whether the whole grid was replaced this frame (by a load or a reconfigure),
+    /// in which case individual changes aren't recorded.
+    pub fn zone_replaced(&self) -> bool {
+        self.zone_replaced
+    }
+}
+
+fn clear_tile_journal(mut journal: ResMut<TileJournal>) {
+    // checked first so an idle frame doesn't mark the journal as changed
+    if !journal.changes.is_empty() || journal.zone_replaced {
+        journal.changes.clear();
+        journal.published = 0;
+        journal.zone_replaced = false;
+    }
+}
+
+fn publish_tile_changes(
+    mut journal: ResMut<TileJournal>,
+    mut tile_changed: EventWriter<TileChanged>,
+) {
+    if journal.published < journal.changes.len() {
+        tile_changed.send_batch(journal.changes[journal.published..].iter().copied());
+        journal.published = journal.changes.len();
+    }
+}
+
+/// Marks entities that only make sense in the zone they were spawned in.
+/// They get despawned when the zone is replaced.
+#[derive(Component)]
+pub struct ZoneScoped;
+
+/// Sent every frame that an in-flight regeneration has made progress.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct ZoneGenerationProgress {
+    /// Fraction of the work done, from 0.0 to 1.0
+    pub fraction: f32,
+}
+
+/// Sent once a regenerated zone has replaced the `Grid<TileState>` resource,
+/// alongside `ZoneRegenerated`.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct ZoneGenerated {
+    pub seed: u32,
+}
+
+/// Tracks the regeneration currently running on the `AsyncComputeTaskPool`, if any.
+#[derive(Resource, Default)]
+struct ZoneGeneration {
+    /// Finishes with `None` if it was cancelled
+    task: Option<Task<Option<GeneratedZone>>>,
+    /// Bits of the latest `f32` progress fraction, written from the task
+    progress: Arc<AtomicU32>,
+    /// Set to stop the task, which checks it whenever it reports progress
+    cancelled: Arc<AtomicBool>,
+    reported_progress: f32,
+}
+
+struct GeneratedZone {
+    dimensions: GridDimensions,
+    tiles: Grid<TileState>,
+    seed: u32,
+}
+
+impl ZoneGeneration {
+    /// Starts generating a zone in the background. Any generation that was already
+    /// running is cancelled, so bursts of requests coalesce into the latest one.
+    fn start(&mut self, dims: GridDimensions, mut noise: Noise, config: IslandConfig) {
+        self.cancel();
+        noise.reseed();
+        let progress = Arc::new(AtomicU32::new(0f32.to_bits()));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (task_progress, task_cancelled) = (progress.clone(), cancelled.clone());
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let mut tiles = Grid::new_from_dims(&dims);
+            let finished = generate_island_with_progress(&dims, &noise, &config, &mut tiles, TileState::from, &mut |fraction| {
+                task_progress.store(fraction.to_bits(), Ordering::Relaxed);
+                if task_cancelled.load(Ordering::Relaxed) { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+            });
+            finished.is_continue().then(|| GeneratedZone { dimensions: dims, tiles, seed: noise.seed() })
+        });
+
+        self.task = Some(task);
+        self.progress = progress;
+        self.cancelled = cancelled;
+        self.reported_progress = 0.;
+    }
+
+    fn cancel(&mut self) {
+        // dropping the task only cancels it if it hasn't started running yet; once it has,
+        // it runs until it next reports progress and sees the flag
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.task = None;
+    }
+}
+
+/// A new zone waiting to be swapped in during `ZoneSystems::Apply`.
+#[derive(Resource, Default)]
+struct StagedZone(Option<NextZone>);
+
+enum NextZone {
+    Generated(GeneratedZone),
+    Loaded(ZoneSave),
 }
 
 fn startup_init_zone(
     dimensions: Res<GridDimensions>,
     mut tiles: ResMut<Grid<TileState>>,
     zone_noise: Res<ZoneNoise>,
+    config: Res<IslandConfig>,
 ) {
-    generate_island_into(&dimensions, &zone_noise.0, tiles.deref_mut(), TileState::from);
+    generate_island_into(&dimensions, &zone_noise.0, &config, tiles.deref_mut(), TileState::from);
 }
 
 fn handle_zone_commands(
     mut zone_commands: EventReader<ZoneCommand>,
     dimensions: Res<GridDimensions>,
     mut tiles: ResMut<Grid<TileState>>,
-    mut zone_noise: ResMut<ZoneNoise>,
+    zone_noise: Res<ZoneNoise>,
+    config: Res<IslandConfig>,
+    mut history: ResMut<EditHistory<TileState>>,
+    mut journal: ResMut<TileJournal>,
+    mut generation: ResMut<ZoneGeneration>,
+    mut staged: ResMut<StagedZone>,
+    mut will_regenerate: EventWriter<ZoneWillRegenerate>,
 ) {
     for cmd in zone_commands.iter() {
         match cmd {
             ZoneCommand::Regenerate => {
-                zone_noise.0.reseed();
-                generate_island_into(&dimensions, &zone_noise.0, &mut tiles, TileState::from);
+                generation.start(*dimensions, zone_noise.0.clone(), *config);
+            },
+            ZoneCommand::Reconfigure { size, tile_size, bottom_left } => {
+                let dims = GridDimensions {
+                    size_in_tiles: *size,
+                    tile_size: *tile_size,
+                    bottom_left: *bottom_left,
+                };
+                generation.start(dims, zone_noise.0.clone(), *config);
+            },
+            ZoneCommand::SetTile(address, tile) => {
+                let mut edit = TileEdit::new();
+                edit.set(&mut tiles, *address, *tile);
+                journal.record_edit(&edit);
+                history.record(edit);
             },
+            ZoneCommand::SetTiles(addresses, tile) => {
+                let mut edit = TileEdit::new();
+                for address in addresses {
+                    edit.set(&mut tiles, *address, *tile);
+                }
+                journal.record_edit(&edit);
+                history.record(edit);
+            },
+            ZoneCommand::FillRect { min, max, tile } => {
+                let mut edit = TileEdit::new();
+                for addr in TileRect::from_corners(*min, *max).addresses() {
+                    edit.set(&mut tiles, addr, *tile);
+                }
+                journal.record_edit(&edit);
+                history.record(edit);
+            },
+            ZoneCommand::FloodFillAt(address, tile) => {
+                // filling with the start tile's own type would never terminate, and wouldn't change anything anyway
+                if tiles.tile_at(address).is_some_and(|t| t != tile) {
+                    let mut edit = TileEdit::new();
+                    let truncated = flood_fill_with(
+                        &mut EditRecorder { grid: &mut tiles, edit: &mut edit },
+                        address.as_tuple(),
+                        |a, b| a == b,
+                        *tile,
+                        &FillOptions { budget: Some(BUCKET_FILL_BUDGET), ..default() },
+                    );
+                    if truncated {
+                        println!("bucket fill stopped after {} tiles", BUCKET_FILL_BUDGET);
+                    }
+                    journal.record_edit(&edit);
+                    history.record(edit);
+                }
+            },
+            ZoneCommand::PasteGrid { grid, offset } => {
+                let mut edit = TileEdit::new();
+                for addr in grid.addresses() {
+                    if let (Some(x), Some(y)) = (addr.0.checked_add(offset.0), addr.1.checked_add(offset.1)) {
+                        edit.set(&mut tiles, TileAddress(x, y), grid[addr]);
+                    }
+                }
+                journal.record_edit(&edit);
+                history.record(edit);
+            },
+            ZoneCommand::BeginEditGroup => {
+                history.begin_group();
+            },
+            ZoneCommand::EndEditGroup => {
+                history.end_group();
+            },
+            ZoneCommand::Undo => {
+                if history.can_undo() {
+                    if let Some(edit) = history.undo(&mut tiles) {
+                        journal.record_undo(edit);
+                    }
+                }
+            },
+            ZoneCommand::Redo => {
+                if history.can_redo() {
+                    if let Some(edit) = history.redo(&mut tiles) {
+                        journal.record_edit(edit);
+                    }
+                }
+            },
+            ZoneCommand::Save(path) => {
+                let name = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+                let save = ZoneSave {
+                    metadata: ZoneMetadata::now(name),
+                    dimensions: *dimensions,
+                    seed: zone_noise.0.seed(),
+                    config: *config,
+                    tiles: tiles.clone(),
+                };
+                match save_zone(path, &save) {
+                    Ok(()) => println!("saved zone to {:?}", path),
+                    Err(e) => println!("failed to save zone to {:?}: {}", path, e),
+                }
+            },
+            ZoneCommand::Load(path) => {
+                match load_zone_file(path, &dimensions, zone_noise.0.seed(), &config) {
+                    Ok(save) => {
+                        println!("loaded zone {:?} from {:?}", save.metadata.name, path);
+                        // a regeneration finishing later would clobber the loaded zone
+                        generation.cancel();
+                        staged.0 = Some(NextZone::Loaded(save));
+                        will_regenerate.send(ZoneWillRegenerate);
+                    },
+                    Err(e) => println!("failed to load zone from {:?}: {}", path, e),
+                }
+            },
+        }
+    }
+}
+
+fn finish_zone_generation(
+    mut generation: ResMut<ZoneGeneration>,
+    mut staged: ResMut<StagedZone>,
+    mut progress_events: EventWriter<ZoneGenerationProgress>,
+    mut will_regenerate: EventWriter<ZoneWillRegenerate>,
+) {
+    let Some(task) = generation.task.as_mut() else { return };
+    let finished = future::block_on(future::poll_once(task));
+
+    let fraction = f32::from_bits(generation.progress.load(Ordering::Relaxed));
+    if fraction > generation.reported_progress {
+        generation.reported_progress = fraction;
+        progress_events.send(ZoneGenerationProgress { fraction });
+    }
+
+    if let Some(result) = finished {
+        generation.task = None;
+        if let Some(generated) = result {
+            staged.0 = Some(NextZone::Generated(generated));
+            will_regenerate.send(ZoneWillRegenerate);
         }
     }
 }
 
-#[derive(Component, Copy, Clone, Debug, Default, Eq, PartialEq)]
+fn despawn_zone_scoped(
+    mut will_regenerate: EventReader<ZoneWillRegenerate>,
+    scoped: Query<Entity, With<ZoneScoped>>,
+    mut commands: Commands,
+) {
+    if will_regenerate.iter().count() == 0 {
+        return;
+    }
+    for entity in &scoped {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn apply_staged_zone(
+    mut staged: ResMut<StagedZone>,
+    mut tiles: ResMut<Grid<TileState>>,
+    dimensions: Res<GridDimensions>,
+    mut zone_noise: ResMut<ZoneNoise>,
+    mut history: ResMut<EditHistory<TileState>>,
+    mut journal: ResMut<TileJournal>,
+    mut commands: Commands,
+    mut regenerated_events: EventWriter<ZoneRegenerated>,
+    mut generated_events: EventWriter<ZoneGenerated>,
+) {
+    let Some(next_zone) = staged.0.take() else { return };
+    // edits to the old zone can't be undone in the new one
+    history.clear();
+    match next_zone {
+        NextZone::Generated(generated) => {
+            if generated.dimensions == *dimensions {
+                for addr in tiles.addresses() {
+                    journal.record(addr, tiles[addr], generated.tiles[addr]);
+                }
+                // swap in the whole grid at once, so nothing ever sees a half-generated zone
+                *tiles = generated.tiles;
+            } else {
+                // a reconfigured zone needs its sprites and borders rebuilt, same as a loaded one
+                journal.zone_replaced = true;
+                commands.remove_resource::<Grid<TileState>>();
+                commands.insert_resource(generated.tiles);
+                commands.insert_resource(generated.dimensions);
+            }
+            zone_noise.0.set_seed(generated.seed);
+            generated_events.send(ZoneGenerated { seed: generated.seed });
+        },
+        NextZone::Loaded(save) => {
+            journal.zone_replaced = true;
+            zone_noise.0.set_seed(save.seed);
+            // Removing the grid before inserting the loaded one makes it count as
+            // newly added, so the tile sprites and borders get rebuilt from scratch.
+            commands.remove_resource::<Grid<TileState>>();
+            commands.insert_resource(save.tiles);
+            commands.insert_resource(save.dimensions);
+            commands.insert_resource(save.config);
+        },
+    }
+    regenerated_events.send(ZoneRegenerated);
+}
+
+/// The most tiles a single bucket fill can change, so a misclick on a huge zone can't stall the game
+pub const BUCKET_FILL_BUDGET: usize = 1 << 16;
+
+/// How far from the water, in tiles, `spawn_point` tries to put the player
+pub const SPAWN_CLEARANCE: f32 = 2.0;
+
+/// Picks where to put the player in a fresh zone: the floor tile closest to the middle of the zone
+/// that's at least `SPAWN_CLEARANCE` tiles from the water, or failing that, any floor tile.
+pub fn spawn_point(tiles: &Grid<TileState>, dimensions: &GridDimensions) -> Option<TileAddress> {
+    let center = TileAddress(dimensions.size_in_tiles[0] / 2, dimensions.size_in_tiles[1] / 2);
+    let clearance = distance_transform(tiles, TileState::is_floor);
+    clearance.nearest_where(center, |distance| *distance >= SPAWN_CLEARANCE)
+        .or_else(|| tiles.nearest_where(center, TileState::is_floor))
+}
+
+/// How many pixels across each tile is drawn as, when zones are exported to (and imported from) PNG
+pub const ZONE_PNG_SCALE: u32 = 8;
+
+/// Loads a zone save, or imports a zone from a PNG or Tiled map. Imported formats don't
+/// record generator settings, so those (and the PNG's tile size and origin) are kept from the current zone.
+fn load_zone_file(path: &Path, dimensions: &GridDimensions, seed: u32, config: &IslandConfig) -> io::Result<ZoneSave> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    let name = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+    match extension {
+        "png" => {
+            let tiles = grid_image::load_png(path, &TileState::palette(), ZONE_PNG_SCALE)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let mut dimensions = *dimensions;
+            dimensions.size_in_tiles = [tiles.width(), tiles.height()];
+            Ok(ZoneSave {
+                metadata: ZoneMetadata::now(name),
+                dimensions,
+                seed,
+                config: *config,
+                tiles,
+            })
+        },
+        "tmj" => {
+            let imported = tiled::import_zone(path, &TileState::gid_mapping())?;
+            Ok(ZoneSave {
+                metadata: ZoneMetadata::now(name),
+                dimensions: imported.dimensions,
+                seed,
+                config: *config,
+                tiles: imported.grid,
+            })
+        },
+        _ => load_zone(path),
+    }
+}
+
+#[derive(Component, Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub enum TileState {
     #[default]
     Floor,
@@ -61,6 +556,18 @@ pub enum TileState {
 }
 
 impl TileState {
+    pub const ALL: [TileState; 2] = [TileState::Floor, TileState::Water];
+
+    pub fn palette() -> Palette<TileState> {
+        Palette::new(TileState::ALL, TileState::as_color)
+    }
+
+    pub fn gid_mapping() -> GidMapping<TileState> {
+        GidMapping::new(TileState::Water)
+            .with(1, TileState::Floor)
+            .with(2, TileState::Water)
+    }
+
     pub fn as_color(&self) -> Color {
         match self {
             TileState::Floor => Color::WHITE,
@@ -86,7 +593,7 @@ impl From<Reachability> for TileState {
 }
 
 
-#[derive(Copy, Clone, Resource)]
+#[derive(Copy, Clone, Debug, PartialEq, Resource)]
 pub struct GridDimensions {
     pub size_in_tiles: [u32; 2],
     pub tile_size: f32,
@@ -128,6 +635,99 @@ impl GridDimensions {
         }
     }
 
+    /// Like `world_pos_of`, but for tiles of an unbounded grid whose (0, 0) tile sits at `bottom_left`.
+    pub fn world_pos_of_signed(&self, tile: &SignedTileAddress) -> Vec2 {
+        self.bottom_left + Vec2::new(
+            tile.0 as f32 * self.tile_size,
+            tile.1 as f32 * self.tile_size,
+        )
+    }
+
+    /// Like `position_to_address`, but without the bounds check, for tiles of an unbounded grid.
+    pub fn position_to_signed_address(&self, position: Vec2) -> SignedTileAddress {
+        let rel_pos = ((position - self.bottom_left) / self.tile_size).floor();
+        SignedTileAddress(rel_pos.x as i32, rel_pos.y as i32)
+    }
+
+    /// The tiles overlapping a world-space rectangle, including ones that only touch its edges
+    pub fn tiles_in_rect(&self, rect: Rect) -> impl Iterator<Item=TileAddress> {
+        let [width, height] = self.size_in_tiles;
+        let to_tiles = |p: Vec2| (p - self.bottom_left) / self.tile_size;
+        // a tile whose far edge is exactly on `rect.min` still touches the rect
+        let (min, max) = (to_tiles(rect.min).ceil() - Vec2::ONE, to_tiles(rect.max).floor());
+        let clamp = |v: f32, size: u32| v.clamp(0., size as f32 - 1.) as u32;
+        let outside = width == 0 || height == 0 || min.x > max.x || min.y > max.y
+            || max.x < 0. || max.y < 0. || min.x >= width as f32 || min.y >= height as f32;
+        let (x_range, y_range) = if outside {
+            (0..0, 0..0)
+        } else {
+            (clamp(min.x, width)..clamp(max.x, width) + 1, clamp(min.y, height)..clamp(max.y, height) + 1)
+        };
+        y_range.flat_map(move |y| x_range.clone().map(move |x| TileAddress(x, y)))
+    }
+
+    /// The tiles overlapping a world-space circle
+    pub fn tiles_in_circle(&self, center: Vec2, radius: f32) -> impl Iterator<Item=TileAddress> + '_ {
+        let bounds = Rect::from_center_half_size(center, Vec2::splat(radius));
+        self.tiles_in_rect(bounds).filter(move |addr| {
+            let min = self.world_pos_of(addr);
+            let nearest = center.clamp(min, min + Vec2::splat(self.tile_size));
+            nearest.distance_squared(center) <= radius * radius
+        })
+    }
+
+    /// The tiles a world-space line segment passes through, in order from `from` to `to`. Where the
+    /// segment passes exactly through a tile corner, both tiles beside the corner are included
+    /// (a "supercover" line), so nothing can slip through diagonally.
+    pub fn tiles_on_segment(&self, from: Vec2, to: Vec2) -> impl Iterator<Item=TileAddress> {
+        let start = (from - self.bottom_left) / self.tile_size;
+        let end = (to - self.bottom_left) / self.tile_size;
+        let delta = end - start;
+        let (mut x, mut y) = (start.x.floor() as i64, start.y.floor() as i64);
+        let (end_x, end_y) = (end.x.floor() as i64, end.y.floor() as i64);
+        let step_x = if delta.x > 0. { 1 } else { -1 };
+        let step_y = if delta.y > 0. { 1 } else { -1 };
+
+        // how far along the segment (0 to 1) the next vertical and horizontal tile boundaries are crossed
+        let first_crossing = |pos: f32, tile: i64, step: i64, d: f32| {
+            if d == 0. { f32::INFINITY } else { ((tile + (step > 0) as i64) as f32 - pos) / d }
+        };
+        let mut next_x = first_crossing(start.x, x, step_x, delta.x);
+        let mut next_y = first_crossing(start.y, y, step_y, delta.y);
+        let every_x = if delta.x == 0. { f32::INFINITY } else { 1. / delta.x.abs() };
+        let every_y = if delta.y == 0. { f32::INFINITY } else { 1. / delta.y.abs() };
+
+        let mut tiles = vec![(x, y)];
+        let steps = (end_x - x).abs() + (end_y - y).abs();
+        for _ in 0..steps {
+            if (x, y) == (end_x, end_y) {
+                break;
+            }
+            if (next_x - next_y).abs() < 1e-6 {
+                tiles.push((x + step_x, y));
+                tiles.push((x, y + step_y));
+                x += step_x;
+                y += step_y;
+                next_x += every_x;
+                next_y += every_y;
+            } else if next_x < next_y {
+                x += step_x;
+                next_x += every_x;
+            } else {
+                y += step_y;
+                next_y += every_y;
+            }
+            tiles.push((x, y));
+        }
+
+        let [width, height] = self.size_in_tiles;
+        tiles.into_iter().filter_map(move |(x, y)| {
+            let x = u32::try_from(x).ok().filter(|x| *x < width)?;
+            let y = u32::try_from(y).ok().filter(|y| *y < height)?;
+            Some(TileAddress(x, y))
+        })
+    }
+
     // return a new Vec2 which represents the given `point`'s position relative to
     // the `world_center`, scaled relative to size of the grid, such that for a
     // `point` inside the grid, the magnitude of the x and y components of the returned
@@ -139,4 +739,104 @@ impl GridDimensions {
             y: 2.0 * (y - self.bottom_left.y) / self.world_height() - 1.0,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    // 10x8 tiles of 2x2 world units, with the grid's corner at (-4, -2)
+    fn dims() -> GridDimensions {
+        GridDimensions {
+            size_in_tiles: [10, 8],
+            tile_size: 2.,
+            bottom_left: Vec2::new(-4., -2.),
+        }
+    }
+
+    fn rect(min: [f32; 2], max: [f32; 2]) -> Rect {
+        Rect { min: min.into(), max: max.into() }
+    }
+
+    fn tiles(addrs: impl IntoIterator<Item=TileAddress>) -> HashSet<(u32, u32)> {
+        addrs.into_iter().map(|addr| addr.as_tuple()).collect()
+    }
+
+    #[test]
+    fn rects_include_every_tile_they_touch() {
+        let dims = dims();
+        // a point inside a tile, and a point on the corner of four
+        assert_eq!(tiles(dims.tiles_in_rect(rect([1., 1.], [1., 1.]))), HashSet::from([(2, 1)]));
+        assert_eq!(tiles(dims.tiles_in_rect(rect([0., 0.], [0., 0.]))), HashSet::from([(1, 0), (2, 0), (1, 1), (2, 1)]));
+        // a vertical line
+        assert_eq!(tiles(dims.tiles_in_rect(rect([1., 1.], [1., 5.]))), HashSet::from([(2, 1), (2, 2), (2, 3)]));
+        // exactly one tile, which also touches its neighbors
+        assert_eq!(dims.tiles_in_rect(rect([0., 0.], [2., 2.])).count(), 9);
+        // inside out
+        assert_eq!(dims.tiles_in_rect(rect([3., 3.], [1., 1.])).count(), 0);
+    }
+
+    #[test]
+    fn rects_are_clipped_to_the_grid() {
+        let dims = dims();
+        assert_eq!(dims.tiles_in_rect(rect([-100., -100.], [100., 100.])).count(), 80);
+        assert_eq!(tiles(dims.tiles_in_rect(rect([-10., -10.], [-3., -1.]))), HashSet::from([(0, 0)]));
+        assert_eq!(tiles(dims.tiles_in_rect(rect([15., 13.], [30., 30.]))), HashSet::from([(9, 7)]));
+        assert_eq!(dims.tiles_in_rect(rect([-10., -10.], [-5., 30.])).count(), 0);
+        assert_eq!(dims.tiles_in_rect(rect([16.5, -10.], [30., 30.])).count(), 0);
+        assert_eq!(GridDimensions::new([0, 5]).tiles_in_rect(rect([0., 0.], [3., 3.])).count(), 0);
+    }
+
+    #[test]
+    fn circles_of_radius_zero_cover_the_tiles_under_their_center() {
+        let dims = dims();
+        assert_eq!(tiles(dims.tiles_in_circle(Vec2::new(1., 1.), 0.)), HashSet::from([(2, 1)]));
+        assert_eq!(tiles(dims.tiles_in_circle(Vec2::new(0., 0.), 0.)), HashSet::from([(1, 0), (2, 0), (1, 1), (2, 1)]));
+        assert_eq!(dims.tiles_in_circle(Vec2::new(-5., 0.), 0.).count(), 0);
+    }
+
+    #[test]
+    fn circles_leave_out_the_corners_of_their_bounds() {
+        let dims = dims();
+        // centered on the corner between four tiles, reaching just over one tile out
+        let circle = tiles(dims.tiles_in_circle(Vec2::new(2., 2.), 2.5));
+        assert_eq!(circle.len(), 12);
+        assert!(!circle.contains(&(1, 0)) && !circle.contains(&(4, 3)));
+        // and clipped where it overhangs the grid
+        let clipped = tiles(dims.tiles_in_circle(Vec2::new(-4., -2.), 2.5));
+        assert_eq!(clipped, HashSet::from([(0, 0), (1, 0), (0, 1)]));
+    }
+
+    #[test]
+    fn steep_segments_step_one_tile_at_a_time() {
+        let dims = GridDimensions::new([8, 8]);
+        let line: Vec<_> = dims.tiles_on_segment(Vec2::new(0.5, 0.5), Vec2::new(1.5, 6.5)).map(|a| a.as_tuple()).collect();
+        assert_eq!(line, vec![(0, 0), (0, 1), (0, 2), (0, 3), (1, 3), (1, 4), (1, 5), (1, 6)]);
+    }
+
+    #[test]
+    fn negative_slopes_through_corners_cover_both_sides() {
+        let dims = GridDimensions::new([8, 8]);
+        let line: Vec<_> = dims.tiles_on_segment(Vec2::new(0.5, 4.5), Vec2::new(4.5, 0.5)).map(|a| a.as_tuple()).collect();
+        assert_eq!(line, vec![
+            (0, 4), (1, 4), (0, 3), (1, 3), (2, 3), (1, 2), (2, 2),
+            (3, 2), (2, 1), (3, 1), (4, 1), (3, 0), (4, 0),
+        ]);
+        let backward = tiles(dims.tiles_on_segment(Vec2::new(4.5, 0.5), Vec2::new(0.5, 4.5)));
+        assert_eq!(backward, line.into_iter().collect());
+    }
+
+    #[test]
+    fn segments_are_clipped_to_the_grid() {
+        let dims = GridDimensions::new([5, 5]);
+        let line: Vec<_> = dims.tiles_on_segment(Vec2::new(-3.5, 2.5), Vec2::new(2.5, 2.5)).map(|a| a.as_tuple()).collect();
+        assert_eq!(line, vec![(0, 2), (1, 2), (2, 2)]);
+        let line: Vec<_> = dims.tiles_on_segment(Vec2::new(3.5, 3.5), Vec2::new(3.5, 9.5)).map(|a| a.as_tuple()).collect();
+        assert_eq!(line, vec![(3, 3), (3, 4)]);
+        assert_eq!(dims.tiles_on_segment(Vec2::new(-3., -1.), Vec2::new(-1., -3.)).count(), 0);
+        // a segment of zero length is just the tile it's in
+        assert_eq!(tiles(dims.tiles_on_segment(Vec2::new(1.5, 1.5), Vec2::new(1.5, 1.5))), HashSet::from([(1, 1)]));
+    }
+}